@@ -5,7 +5,14 @@
 use std::ffi;
 use std::fs;
 use std::io::{self, Read};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+
+use nalgebra_glm::Vec3;
+
+use crate::geometry::{
+    primitives::Mesh,
+    surfaces::{BezierSurface, UvLayout},
+};
 
 #[derive(Debug)]
 pub enum Error {
@@ -15,6 +22,16 @@ pub enum Error {
     FailedToGetExePath,
     FailedToGetExeDir,
     DirDoesNotExist,
+    Parse { line: usize, message: String },
+}
+
+impl Error {
+    fn parse(line: usize, message: impl Into<String>) -> Self {
+        Error::Parse {
+            line,
+            message: message.into(),
+        }
+    }
 }
 
 impl From<io::Error> for Error {
@@ -83,4 +100,153 @@ impl AssetManager {
         file.read_to_end(&mut buffer)?;
         Ok(buffer)
     }
+
+    /// Reads bicubic Bezier patches, each tessellated with `edges` vertices per side.
+    pub fn read_bezier_patches(
+        &self,
+        asset_path: &str,
+        edges: usize,
+    ) -> Result<Vec<BezierSurface<4, 4>>, Error> {
+        Ok(self
+            .read_control_grids(asset_path)?
+            .into_iter()
+            .map(|grid| BezierSurface::new(grid, edges))
+            .collect())
+    }
+
+    /// Reads the control grids of bicubic Bezier patches, from the Newell format if the
+    /// extension is `.bpt` and from a list of comma separated control points otherwise.
+    pub fn read_control_grids(&self, asset_path: &str) -> Result<Vec<[[Vec3; 4]; 4]>, Error> {
+        let source = self.read_string(asset_path)?;
+        if Path::new(asset_path)
+            .extension()
+            .is_some_and(|e| e == "bpt")
+        {
+            parse_bpt(&source)
+        } else {
+            parse_bezier_points(&source)
+        }
+    }
+
+    /// Reads a multi-patch Bezier model and tessellates it into a single mesh.
+    /// Without a uv `layout` the vertices along the seams between patches are shared,
+    /// otherwise they are only shared where their texture coordinates match.
+    pub fn read_bezier_model(
+        &self,
+        asset_path: &str,
+        edges: usize,
+        layout: Option<UvLayout>,
+    ) -> Result<Mesh, Error> {
+        let grids = self.read_control_grids(asset_path)?;
+        let mut mesh = Mesh {
+            vertices: Vec::new(),
+            indices: Vec::new(),
+        };
+        for (index, grid) in grids.iter().enumerate() {
+            let mut patch_mesh = BezierSurface::new(*grid, edges).into_mesh();
+            if let Some(layout) = layout {
+                layout.apply(&mut patch_mesh, index, grids.len());
+            }
+            mesh.append(patch_mesh);
+        }
+        match layout {
+            Some(_) => mesh.weld_matching_uv(SEAM_TOLERANCE),
+            None => mesh.weld(SEAM_TOLERANCE),
+        }
+        Ok(mesh)
+    }
+}
+
+const SEAM_TOLERANCE: f32 = 1e-4;
+
+/// Parses one control point per line as `x,y,z`, every 16 points making the control
+/// grid of a patch stored row after row.
+pub fn parse_bezier_points(source: &str) -> Result<Vec<[[Vec3; 4]; 4]>, Error> {
+    let mut points = Vec::new();
+    let mut last_line = 0;
+    for (index, line) in source.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        last_line = index + 1;
+        points.push(parse_point(line.split(','), last_line)?);
+    }
+    if points.len() % 16 != 0 {
+        return Err(Error::parse(
+            last_line,
+            format!(
+                "{} control points do not make complete 4x4 patches",
+                points.len()
+            ),
+        ));
+    }
+    Ok(points.chunks_exact(16).map(patch_from_points).collect())
+}
+
+/// Parses the Newell `.bpt` format: the number of patches, then for every patch
+/// a line with its degrees followed by its control points, one `x y z` per line.
+pub fn parse_bpt(source: &str) -> Result<Vec<[[Vec3; 4]; 4]>, Error> {
+    let mut lines = source
+        .lines()
+        .enumerate()
+        .map(|(index, line)| (index + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty());
+    let mut next_line = |expected: &str| {
+        lines
+            .next()
+            .ok_or_else(|| Error::parse(source.lines().count(), format!("missing {}", expected)))
+    };
+
+    let (line, count) = next_line("patch count")?;
+    let count: usize = count
+        .parse()
+        .map_err(|_| Error::parse(line, format!("invalid patch count '{}'", count)))?;
+
+    let mut patches = Vec::new();
+    for _ in 0..count {
+        let (line, degrees) = next_line("patch degrees")?;
+        let degrees: Vec<&str> = degrees.split_whitespace().collect();
+        if degrees != ["3", "3"] {
+            return Err(Error::parse(
+                line,
+                format!(
+                    "only bicubic patches are supported, found degrees {:?}",
+                    degrees
+                ),
+            ));
+        }
+        let mut points = Vec::with_capacity(16);
+        for _ in 0..16 {
+            let (line, point) = next_line("control point")?;
+            points.push(parse_point(point.split_whitespace(), line)?);
+        }
+        patches.push(patch_from_points(&points));
+    }
+    Ok(patches)
+}
+
+fn parse_point<'a>(mut fields: impl Iterator<Item = &'a str>, line: usize) -> Result<Vec3, Error> {
+    let mut coordinates = [0.0; 3];
+    for coordinate in coordinates.iter_mut() {
+        let field = fields
+            .next()
+            .ok_or_else(|| Error::parse(line, "expected 3 coordinates"))?
+            .trim();
+        *coordinate = field
+            .parse()
+            .map_err(|_| Error::parse(line, format!("invalid coordinate '{}'", field)))?;
+    }
+    if fields.next().is_some() {
+        return Err(Error::parse(line, "expected 3 coordinates"));
+    }
+    Ok(Vec3::new(coordinates[0], coordinates[1], coordinates[2]))
+}
+
+fn patch_from_points(points: &[Vec3]) -> [[Vec3; 4]; 4] {
+    let mut grid = [[Vec3::zeros(); 4]; 4];
+    for (i, row) in grid.iter_mut().enumerate() {
+        row.copy_from_slice(&points[i * 4..(i + 1) * 4]);
+    }
+    grid
 }
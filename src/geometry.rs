@@ -6,5 +6,15 @@ pub mod splines;
 pub mod primitives;
 pub mod surfaces;
 pub mod algorithms;
+pub mod processing;
+pub mod simplification;
+pub mod half_edge;
+pub mod subdivision;
+pub mod shapes;
+pub mod sweeps;
+pub mod bounds;
+pub mod bvh;
+pub mod picking;
+pub mod frustum;
 
-use nalgebra_glm::{Vec2, Vec3};
+use nalgebra_glm::{Mat3, Mat4, Vec2, Vec3, Vec4};
@@ -2,7 +2,14 @@
 * SPDX-License-Identifier: MIT
 */
 
-use super::Vec3;
+use std::cmp::Ordering;
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+
+use nalgebra_glm as glm;
+
+use super::primitives::{Mesh, Vertex};
+use super::{Vec2, Vec3};
 
 pub fn de_casteljau(t: f32, points: &[Vec3]) -> Vec3 {
     let mut points = points.to_vec();
@@ -13,3 +20,616 @@ pub fn de_casteljau(t: f32, points: &[Vec3]) -> Vec3 {
     }
     points[0]
 }
+
+/// Splits the Bezier curve defined by `points` at `t` into two curves of the same degree.
+pub fn subdivide(t: f32, points: &[Vec3]) -> (Vec<Vec3>, Vec<Vec3>) {
+    let mut points = points.to_vec();
+    let n = points.len();
+    let mut left = Vec::with_capacity(n);
+    let mut right = Vec::with_capacity(n);
+    left.push(points[0]);
+    right.push(points[n - 1]);
+    for i in 1..n {
+        for j in 0..(n - i) {
+            points[j] = points[j] * (1.0 - t) + points[j + 1] * t;
+        }
+        left.push(points[0]);
+        right.push(points[n - i - 1]);
+    }
+    right.reverse();
+    (left, right)
+}
+
+/// Control points of the same curve expressed with one degree more.
+pub fn elevate_degree(points: &[Vec3]) -> Vec<Vec3> {
+    let n = points.len();
+    let mut elevated = Vec::with_capacity(n + 1);
+    elevated.push(points[0]);
+    for i in 1..n {
+        let a = i as f32 / n as f32;
+        elevated.push(points[i - 1] * a + points[i] * (1.0 - a));
+    }
+    elevated.push(points[n - 1]);
+    elevated
+}
+
+/// Approximates the curve with one degree less, keeping both end points. The first half of
+/// the control points is solved from the start of the curve and the second half from its end.
+pub fn reduce_degree(points: &[Vec3]) -> Vec<Vec3> {
+    let n = points.len() - 1;
+    let m = n - 1;
+    let mut forward = vec![points[0]; n];
+    for i in 1..n {
+        forward[i] = (points[i] * n as f32 - forward[i - 1] * i as f32) / (n - i) as f32;
+    }
+    let mut backward = vec![points[n]; n];
+    for i in (1..n).rev() {
+        backward[i - 1] = (points[i] * n as f32 - backward[i] * (n - i) as f32) / i as f32;
+    }
+    (0..=m)
+        .map(|i| match (2 * i).cmp(&m) {
+            Ordering::Less => forward[i],
+            Ordering::Greater => backward[i],
+            Ordering::Equal => (forward[i] + backward[i]) * 0.5,
+        })
+        .collect()
+}
+
+/// Control points of the derivative of the Bezier curve defined by `points`.
+pub fn hodograph(points: &[Vec3]) -> Vec<Vec3> {
+    if points.len() < 2 {
+        return vec![Vec3::zeros()];
+    }
+    let degree = (points.len() - 1) as f32;
+    points.windows(2).map(|w| (w[1] - w[0]) * degree).collect()
+}
+
+/// Returns the index `k` of the knot span `[knots[k], knots[k + 1])` containing `t`,
+/// clamped to the valid domain `[knots[degree], knots[n]]` of the spline.
+pub fn find_span(t: f32, degree: usize, knots: &[f32]) -> usize {
+    let n = knots.len() - degree - 1;
+    let mut span = degree;
+    while span + 1 < n && knots[span + 1] <= t {
+        span += 1;
+    }
+    span
+}
+
+pub fn de_boor(t: f32, degree: usize, knots: &[f32], points: &[Vec3]) -> Vec3 {
+    let k = find_span(t, degree, knots);
+    let mut d = points[k - degree..=k].to_vec();
+    for r in 1..=degree {
+        for j in (r..=degree).rev() {
+            let i = j + k - degree;
+            let denom = knots[i + degree + 1 - r] - knots[i];
+            let alpha = if denom == 0.0 {
+                0.0
+            } else {
+                (t - knots[i]) / denom
+            };
+            d[j] = d[j - 1] * (1.0 - alpha) + d[j] * alpha;
+        }
+    }
+    d[degree]
+}
+
+const GAUSS_LEGENDRE_8: [(f32, f32); 4] = [
+    (0.183_434_64, 0.362_683_78),
+    (0.525_532_4, 0.313_706_65),
+    (0.796_666_5, 0.222_381_03),
+    (0.960_289_86, 0.101_228_54),
+];
+
+/// Integral of `f` over `[a, b]` by the 8-point Gauss-Legendre rule.
+pub fn gauss_legendre(a: f32, b: f32, f: impl Fn(f32) -> f32) -> f32 {
+    let (middle, half) = ((a + b) / 2.0, (b - a) / 2.0);
+    GAUSS_LEGENDRE_8
+        .iter()
+        .map(|&(x, w)| w * (f(middle - half * x) + f(middle + half * x)))
+        .sum::<f32>()
+        * half
+}
+
+pub fn binomial(n: usize, k: usize) -> f32 {
+    (0..k).fold(1.0, |acc, i| acc * (n - i) as f32 / (i + 1) as f32)
+}
+
+/// Values of the `degree + 1` non-vanishing basis functions on `span` and of their
+/// derivatives up to `order`: `result[k][j]` is the k-th derivative of `N(span - degree + j)`.
+pub fn basis_functions_derivatives(
+    span: usize,
+    t: f32,
+    degree: usize,
+    knots: &[f32],
+    order: usize,
+) -> Vec<Vec<f32>> {
+    let p = degree;
+    let mut ndu = vec![vec![0.0; p + 1]; p + 1];
+    let mut left = vec![0.0; p + 1];
+    let mut right = vec![0.0; p + 1];
+    ndu[0][0] = 1.0;
+    for j in 1..=p {
+        left[j] = t - knots[span + 1 - j];
+        right[j] = knots[span + j] - t;
+        let mut saved = 0.0;
+        for r in 0..j {
+            ndu[j][r] = right[r + 1] + left[j - r];
+            let temp = ndu[r][j - 1] / ndu[j][r];
+            ndu[r][j] = saved + right[r + 1] * temp;
+            saved = left[j - r] * temp;
+        }
+        ndu[j][j] = saved;
+    }
+
+    let mut ders = vec![vec![0.0; p + 1]; order + 1];
+    for j in 0..=p {
+        ders[0][j] = ndu[j][p];
+    }
+
+    // derivatives of order higher than the degree vanish
+    let order = order.min(p);
+    let mut a = [vec![0.0; p + 1], vec![0.0; p + 1]];
+    for r in 0..=p {
+        let (mut s1, mut s2) = (0, 1);
+        a[0][0] = 1.0;
+        for k in 1..=order {
+            let mut d = 0.0;
+            let rk = r as isize - k as isize;
+            let pk = p - k;
+            if r >= k {
+                a[s2][0] = a[s1][0] / ndu[pk + 1][r - k];
+                d = a[s2][0] * ndu[r - k][pk];
+            }
+            let j1 = if rk >= -1 { 1 } else { (-rk) as usize };
+            let j2 = if r <= pk + 1 { k - 1 } else { p - r };
+            for j in j1..=j2 {
+                let column = (rk + j as isize) as usize;
+                a[s2][j] = (a[s1][j] - a[s1][j - 1]) / ndu[pk + 1][column];
+                d += a[s2][j] * ndu[column][pk];
+            }
+            if r <= pk {
+                a[s2][k] = -a[s1][k - 1] / ndu[pk + 1][r];
+                d += a[s2][k] * ndu[r][pk];
+            }
+            ders[k][r] = d;
+            std::mem::swap(&mut s1, &mut s2);
+        }
+    }
+
+    let mut factor = p as f32;
+    for (k, ders) in ders.iter_mut().enumerate().take(order + 1).skip(1) {
+        ders.iter_mut().for_each(|value| *value *= factor);
+        factor *= (p - k) as f32;
+    }
+    ders
+}
+
+fn cross_2d(a: &Vec2, b: &Vec2) -> f32 {
+    a.x * b.y - a.y * b.x
+}
+
+/// Twice the signed area of `polygon`, positive when counter-clockwise.
+pub fn signed_area_2d(polygon: &[Vec2]) -> f32 {
+    (0..polygon.len())
+        .map(|i| cross_2d(&polygon[i], &polygon[(i + 1) % polygon.len()]))
+        .sum()
+}
+
+/// Triangulates a simple polygon by ear clipping, the triangles are counter-clockwise
+/// whatever the orientation of the polygon. Returns `None` for self-intersecting polygons.
+pub fn ear_clipping(polygon: &[Vec2]) -> Option<Vec<[usize; 3]>> {
+    let mut remaining: Vec<usize> = (0..polygon.len()).collect();
+    if signed_area_2d(polygon) < 0.0 {
+        remaining.reverse();
+    }
+    let mut triangles = Vec::with_capacity(polygon.len().saturating_sub(2));
+    while remaining.len() > 3 {
+        let count = remaining.len();
+        let ear = (0..count).find(|&i| {
+            let [a, b, c] = [i + count - 1, i, i + 1].map(|k| remaining[k % count]);
+            let (pa, pb, pc) = (polygon[a], polygon[b], polygon[c]);
+            if cross_2d(&(pb - pa), &(pc - pb)) <= 0.0 {
+                return false;
+            }
+            // no other vertex may lie in the ear
+            remaining.iter().all(|&other| {
+                let p = polygon[other];
+                other == a
+                    || other == b
+                    || other == c
+                    || p == pa
+                    || p == pb
+                    || p == pc
+                    || cross_2d(&(pb - pa), &(p - pa)) < 0.0
+                    || cross_2d(&(pc - pb), &(p - pb)) < 0.0
+                    || cross_2d(&(pa - pc), &(p - pc)) < 0.0
+            })
+        });
+        match ear {
+            Some(i) => {
+                let [a, b, c] = [i + count - 1, i, i + 1].map(|k| remaining[k % count]);
+                triangles.push([a, b, c]);
+                remaining.remove(i);
+            }
+            None => {
+                // collinear vertices are dropped, anything else means the polygon is not simple
+                let flat = (0..count).find(|&i| {
+                    let [a, b, c] =
+                        [i + count - 1, i, i + 1].map(|k| polygon[remaining[k % count]]);
+                    cross_2d(&(b - a), &(c - b)) == 0.0
+                })?;
+                remaining.remove(flat);
+            }
+        }
+    }
+    if remaining.len() == 3 {
+        let [a, b, c] = [remaining[0], remaining[1], remaining[2]];
+        if cross_2d(&(polygon[b] - polygon[a]), &(polygon[c] - polygon[b])) > 0.0 {
+            triangles.push([a, b, c]);
+        }
+    }
+    Some(triangles)
+}
+
+/// Solves `matrix * x = rhs` by Gaussian elimination with partial pivoting, every column
+/// of `rhs` being a separate right-hand side. Returns `None` if the matrix is singular.
+pub fn solve_linear_system(
+    mut matrix: Vec<Vec<f32>>,
+    mut rhs: Vec<Vec<f32>>,
+) -> Option<Vec<Vec<f32>>> {
+    let n = matrix.len();
+    let scale = matrix
+        .iter()
+        .flatten()
+        .fold(0.0f32, |max, value| max.max(value.abs()));
+    for column in 0..n {
+        let pivot = (column..n).max_by(|&a, &b| {
+            matrix[a][column]
+                .abs()
+                .partial_cmp(&matrix[b][column].abs())
+                .unwrap_or(Ordering::Equal)
+        })?;
+        if matrix[pivot][column].abs() <= scale * 1e-6 {
+            return None;
+        }
+        matrix.swap(column, pivot);
+        rhs.swap(column, pivot);
+        let (pivot_rows, rows) = matrix.split_at_mut(column + 1);
+        let (pivot_rhs, rows_rhs) = rhs.split_at_mut(column + 1);
+        let (pivot_row, pivot_rhs) = (&pivot_rows[column], &pivot_rhs[column]);
+        for (row, row_rhs) in rows.iter_mut().zip(rows_rhs.iter_mut()) {
+            let factor = row[column] / pivot_row[column];
+            if factor == 0.0 {
+                continue;
+            }
+            for (value, pivot) in row.iter_mut().zip(pivot_row).skip(column) {
+                *value -= factor * pivot;
+            }
+            for (value, pivot) in row_rhs.iter_mut().zip(pivot_rhs) {
+                *value -= factor * pivot;
+            }
+        }
+    }
+    for row in (0..n).rev() {
+        let (rows_rhs, solved) = rhs.split_at_mut(row + 1);
+        let row_rhs = &mut rows_rhs[row];
+        for (factor, solved) in matrix[row][row + 1..].iter().zip(solved.iter()) {
+            for (value, x) in row_rhs.iter_mut().zip(solved) {
+                *value -= factor * x;
+            }
+        }
+        let diagonal = matrix[row][row];
+        row_rhs.iter_mut().for_each(|value| *value /= diagonal);
+    }
+    Some(rhs)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HullError {
+    NotEnoughPoints,
+    /// All points are the same up to the tolerance.
+    Coincident,
+    Collinear,
+    Coplanar,
+}
+
+impl fmt::Display for HullError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HullError::NotEnoughPoints => write!(f, "a hull needs at least four points"),
+            HullError::Coincident => write!(f, "points are coincident"),
+            HullError::Collinear => write!(f, "points are collinear"),
+            HullError::Coplanar => write!(f, "points are coplanar"),
+        }
+    }
+}
+
+/// Hull face, counter-clockwise seen from outside, with the points above it.
+struct HullFace {
+    vertices: [usize; 3],
+    normal: glm::DVec3,
+    offset: f64,
+    outside: Vec<usize>,
+    alive: bool,
+}
+
+impl HullFace {
+    fn new(vertices: [usize; 3], points: &[glm::DVec3]) -> Self {
+        let [a, b, c] = vertices.map(|v| points[v]);
+        let normal = glm::cross(&(b - a), &(c - a));
+        let normal = if normal.norm_squared() > 0.0 {
+            glm::normalize(&normal)
+        } else {
+            normal
+        };
+        Self {
+            vertices,
+            normal,
+            offset: glm::dot(&normal, &a),
+            outside: Vec::new(),
+            alive: true,
+        }
+    }
+
+    fn distance(&self, point: &glm::DVec3) -> f64 {
+        glm::dot(&self.normal, point) - self.offset
+    }
+
+    fn edges(&self) -> [(usize, usize); 3] {
+        let [a, b, c] = self.vertices;
+        [(a, b), (b, c), (c, a)]
+    }
+}
+
+/// Convex hull of `points` by the quickhull algorithm, as a mesh of the hull points with flat
+/// counter-clockwise triangles seen from outside. Points closer than a tolerance relative
+/// to the extent of the set to a hull face, coplanar or duplicate ones, are left out.
+/// Every triangle has its own vertices carrying the normal of its face.
+pub fn quickhull(points: &[Vec3]) -> Result<Mesh, HullError> {
+    if points.len() < 4 {
+        return Err(HullError::NotEnoughPoints);
+    }
+    let points: Vec<glm::DVec3> = points.iter().map(|p| p.cast::<f64>()).collect();
+    let scale = (0..3)
+        .map(|axis| points.iter().map(|p| p[axis].abs()).fold(0.0, f64::max))
+        .sum::<f64>();
+    let tolerance = scale * f32::EPSILON as f64 * 4.0;
+
+    // initial tetrahedron from the most distant extreme points
+    let farthest = |distance: &dyn Fn(&glm::DVec3) -> f64| {
+        (0..points.len())
+            .map(|i| (i, distance(&points[i])))
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+            .unwrap_or((0, 0.0))
+    };
+    let extremes: Vec<usize> = (0..3)
+        .flat_map(|axis| {
+            let by_axis = |a: &usize, b: &usize| points[*a][axis].total_cmp(&points[*b][axis]);
+            [
+                (0..points.len()).min_by(by_axis).unwrap_or(0),
+                (0..points.len()).max_by(by_axis).unwrap_or(0),
+            ]
+        })
+        .collect();
+    let (mut a, mut b, mut span) = (0, 0, 0.0);
+    for &i in &extremes {
+        for &j in &extremes {
+            let distance = glm::distance(&points[i], &points[j]);
+            if distance > span {
+                (a, b, span) = (i, j, distance);
+            }
+        }
+    }
+    if span <= tolerance {
+        return Err(HullError::Coincident);
+    }
+    let direction = glm::normalize(&(points[b] - points[a]));
+    let (c, distance) = farthest(&|p| glm::cross(&(p - points[a]), &direction).norm());
+    if distance <= tolerance {
+        return Err(HullError::Collinear);
+    }
+    let normal = glm::normalize(&glm::cross(
+        &(points[b] - points[a]),
+        &(points[c] - points[a]),
+    ));
+    let (d, distance) = farthest(&|p| glm::dot(&(p - points[a]), &normal).abs());
+    if distance <= tolerance {
+        return Err(HullError::Coplanar);
+    }
+
+    let mut faces = if glm::dot(&(points[d] - points[a]), &normal) < 0.0 {
+        vec![[a, b, c], [a, d, b], [b, d, c], [c, d, a]]
+    } else {
+        vec![[a, c, b], [a, b, d], [b, c, d], [c, a, d]]
+    }
+    .into_iter()
+    .map(|vertices| HullFace::new(vertices, &points))
+    .collect::<Vec<_>>();
+    // face on the left of every directed edge
+    let mut edges: HashMap<(usize, usize), usize> = HashMap::new();
+    for (index, face) in faces.iter().enumerate() {
+        for edge in face.edges() {
+            edges.insert(edge, index);
+        }
+    }
+    let assign =
+        |candidates: &mut dyn Iterator<Item = usize>, faces: &mut [HullFace], targets: &[usize]| {
+            for point in candidates {
+                if let Some(&face) = targets
+                    .iter()
+                    .find(|&&f| faces[f].distance(&points[point]) > tolerance)
+                {
+                    faces[face].outside.push(point);
+                }
+            }
+        };
+    let initial: Vec<usize> = (0..faces.len()).collect();
+    assign(
+        &mut (0..points.len()).filter(|&i| ![a, b, c, d].contains(&i)),
+        &mut faces,
+        &initial,
+    );
+
+    let mut pending: VecDeque<usize> = initial.into_iter().collect();
+    while let Some(start) = pending.pop_front() {
+        if !faces[start].alive || faces[start].outside.is_empty() {
+            continue;
+        }
+        let face = &faces[start];
+        let eye = face
+            .outside
+            .iter()
+            .copied()
+            .max_by(|&p, &q| {
+                face.distance(&points[p])
+                    .total_cmp(&face.distance(&points[q]))
+            })
+            .unwrap_or(face.outside[0]);
+
+        // faces seen from the eye, connected to the start face; faces it is barely above
+        // count too, keeping the hull convex
+        let mut visible = vec![start];
+        let mut seen = vec![start];
+        let mut k = 0;
+        while k < visible.len() {
+            for (from, to) in faces[visible[k]].edges() {
+                if let Some(&neighbour) = edges.get(&(to, from)) {
+                    if !seen.contains(&neighbour) {
+                        seen.push(neighbour);
+                        if faces[neighbour].distance(&points[eye]) > 0.0 {
+                            visible.push(neighbour);
+                        }
+                    }
+                }
+            }
+            k += 1;
+        }
+
+        let mut horizon = Vec::new();
+        let mut orphans = Vec::new();
+        for &index in &visible {
+            for edge in faces[index].edges() {
+                edges.remove(&edge);
+                if !visible.contains(&edges.get(&(edge.1, edge.0)).copied().unwrap_or(index)) {
+                    horizon.push(edge);
+                }
+            }
+            faces[index].alive = false;
+            orphans.append(&mut faces[index].outside);
+        }
+
+        let mut created = Vec::with_capacity(horizon.len());
+        for (from, to) in horizon {
+            let index = faces.len();
+            faces.push(HullFace::new([from, to, eye], &points));
+            for edge in faces[index].edges() {
+                edges.insert(edge, index);
+            }
+            created.push(index);
+        }
+        assign(
+            &mut orphans.into_iter().filter(|&p| p != eye),
+            &mut faces,
+            &created,
+        );
+        pending.extend(created);
+    }
+
+    // every triangle gets its own corners so that the hull is faceted
+    let mut vertices = Vec::new();
+    for face in faces.iter().filter(|face| face.alive) {
+        let normal = face.normal.cast::<f32>();
+        vertices.extend(face.vertices.map(|v| Vertex {
+            position: points[v].cast::<f32>(),
+            normal,
+            uv: Vec2::zeros(),
+        }));
+    }
+    let mesh = Mesh {
+        indices: (0..vertices.len() as u32).collect(),
+        vertices,
+    };
+    Ok(mesh)
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra_glm as glm;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    use super::{quickhull, HullError};
+    use crate::geometry::Vec3;
+
+    #[test]
+    fn quickhull_rejects_degenerate_input() {
+        let grid: Vec<Vec3> = (0..16)
+            .map(|i| Vec3::new((i % 4) as f32, (i / 4) as f32, 0.0))
+            .collect();
+        // a plane tilted away from the axes
+        let rotation = glm::rotation(0.5, &glm::normalize(&Vec3::new(1.0, 1.0, 0.0)));
+        let tilted: Vec<Vec3> = grid
+            .iter()
+            .map(|p| (rotation * p.push(1.0)).xyz())
+            .collect();
+        let line: Vec<Vec3> = (0..8)
+            .map(|i| Vec3::new(1.0, 2.0, 3.0) * i as f32)
+            .collect();
+
+        assert_eq!(
+            quickhull(&grid[..3]).unwrap_err(),
+            HullError::NotEnoughPoints
+        );
+        assert_eq!(
+            quickhull(&[Vec3::repeat(2.0); 6]).unwrap_err(),
+            HullError::Coincident
+        );
+        assert_eq!(quickhull(&line).unwrap_err(), HullError::Collinear);
+        assert_eq!(quickhull(&grid).unwrap_err(), HullError::Coplanar);
+        assert_eq!(quickhull(&tilted).unwrap_err(), HullError::Coplanar);
+    }
+
+    #[test]
+    fn quickhull_of_a_cube() {
+        let mut rng = StdRng::seed_from_u64(25);
+        let mut points: Vec<Vec3> = (0..8)
+            .map(|i| Vec3::new((i & 1) as f32, ((i >> 1) & 1) as f32, (i >> 2) as f32) * 2.0)
+            .collect();
+        // duplicates, points inside and points on the faces are left out
+        points.extend(points.clone());
+        points.extend((0..200).map(|_| Vec3::from_fn(|_, _| rng.gen_range(0.0..2.0))));
+        points.extend((0..50).map(|i| {
+            Vec3::new(
+                rng.gen_range(0.0..2.0),
+                rng.gen_range(0.0..2.0),
+                (i % 2) as f32 * 2.0,
+            )
+        }));
+
+        let hull = quickhull(&points).unwrap();
+        assert_eq!(hull.indices.len(), 36);
+        assert_eq!(hull.vertices.len(), 36);
+        let center = Vec3::repeat(1.0);
+        let mut volume = 0.0;
+        for triangle in hull.indices.chunks_exact(3) {
+            let [a, b, c] = [0, 1, 2].map(|k| hull.vertices[triangle[k] as usize]);
+            for corner in [&a, &b, &c] {
+                assert!(corner.position.iter().all(|&x| x == 0.0 || x == 2.0));
+            }
+            // flat and counter-clockwise seen from outside
+            let normal = glm::normalize(&glm::cross(
+                &(b.position - a.position),
+                &(c.position - a.position),
+            ));
+            for corner in [&a, &b, &c] {
+                assert!(glm::distance(&corner.normal, &normal) < 1e-6);
+            }
+            assert!(glm::dot(&normal, &(a.position - center)) > 0.0);
+            volume += glm::dot(
+                &(a.position - center),
+                &glm::cross(&(b.position - center), &(c.position - center)),
+            ) / 6.0;
+        }
+        assert!((volume - 8.0).abs() < 1e-4);
+    }
+}
@@ -0,0 +1,556 @@
+/*
+* SPDX-License-Identifier: MIT
+*/
+
+use std::borrow::Cow;
+
+use nalgebra_glm as glm;
+
+use super::algorithms::{quickhull, HullError};
+use super::primitives::{Mesh, PolyLine};
+use super::splines::{Bezier, PiecewiseBezier};
+use super::surfaces::BezierSurface;
+use super::{Mat3, Mat4, Vec3, Vec4};
+
+/// Axis aligned bounding box, empty when `min` is greater than `max`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Aabb {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BoundingSphere {
+    pub center: Vec3,
+    pub radius: f32,
+}
+
+/// Oriented bounding box, `axes` being orthonormal and `half_extents` the half sizes
+/// along them.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Obb {
+    pub center: Vec3,
+    pub axes: [Vec3; 3],
+    pub half_extents: Vec3,
+}
+
+/// Geometry enclosed by a set of points: the vertices of a mesh or a polyline, the
+/// control points of a Bezier curve or surface, which contain it by the convex hull property.
+pub trait Bounded {
+    fn bounding_points(&self) -> Cow<'_, [Vec3]>;
+
+    fn aabb(&self) -> Aabb {
+        Aabb::from_points(&self.bounding_points())
+    }
+
+    fn bounding_sphere(&self) -> BoundingSphere {
+        BoundingSphere::from_points(&self.bounding_points())
+    }
+
+    fn obb(&self) -> Obb {
+        Obb::from_points(&self.bounding_points())
+    }
+
+    /// See [`quickhull`].
+    fn convex_hull(&self) -> Result<Mesh, HullError> {
+        quickhull(&self.bounding_points())
+    }
+}
+
+impl Bounded for Mesh {
+    fn bounding_points(&self) -> Cow<'_, [Vec3]> {
+        Cow::Owned(self.vertices.iter().map(|v| v.position).collect())
+    }
+}
+
+impl Bounded for PolyLine {
+    fn bounding_points(&self) -> Cow<'_, [Vec3]> {
+        Cow::Borrowed(&self.points)
+    }
+}
+
+impl<const N: usize> Bounded for Bezier<N> {
+    fn bounding_points(&self) -> Cow<'_, [Vec3]> {
+        Cow::Borrowed(&self.ctrl_points)
+    }
+}
+
+impl<const N: usize> Bounded for PiecewiseBezier<N> {
+    fn bounding_points(&self) -> Cow<'_, [Vec3]> {
+        Cow::Borrowed(&self.ctrl_points)
+    }
+}
+
+impl<const M: usize, const N: usize> Bounded for BezierSurface<M, N> {
+    fn bounding_points(&self) -> Cow<'_, [Vec3]> {
+        Cow::Owned(self.ctrl_grid().iter().flatten().copied().collect())
+    }
+}
+
+fn transform_point(matrix: &Mat4, point: &Vec3) -> Vec3 {
+    let p = matrix * Vec4::new(point.x, point.y, point.z, 1.0);
+    p.xyz() / p.w
+}
+
+fn transform_vector(matrix: &Mat4, vector: &Vec3) -> Vec3 {
+    (matrix * Vec4::new(vector.x, vector.y, vector.z, 0.0)).xyz()
+}
+
+impl Aabb {
+    pub fn new(min: Vec3, max: Vec3) -> Self {
+        Self { min, max }
+    }
+
+    pub fn empty() -> Self {
+        Self {
+            min: Vec3::repeat(f32::INFINITY),
+            max: Vec3::repeat(f32::NEG_INFINITY),
+        }
+    }
+
+    pub fn from_points(points: &[Vec3]) -> Self {
+        let mut aabb = Self::empty();
+        points.iter().for_each(|p| aabb.extend(p));
+        aabb
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.min.x > self.max.x || self.min.y > self.max.y || self.min.z > self.max.z
+    }
+
+    pub fn center(&self) -> Vec3 {
+        (self.min + self.max) * 0.5
+    }
+
+    pub fn half_extents(&self) -> Vec3 {
+        (self.max - self.min) * 0.5
+    }
+
+    pub fn surface_area(&self) -> f32 {
+        if self.is_empty() {
+            return 0.0;
+        }
+        let size = self.max - self.min;
+        2.0 * (size.x * size.y + size.y * size.z + size.z * size.x)
+    }
+
+    /// Corners in the order of the bits of their index, x being the lowest.
+    pub fn corners(&self) -> [Vec3; 8] {
+        std::array::from_fn(|i| {
+            Vec3::new(
+                if i & 1 == 0 { self.min.x } else { self.max.x },
+                if i & 2 == 0 { self.min.y } else { self.max.y },
+                if i & 4 == 0 { self.min.z } else { self.max.z },
+            )
+        })
+    }
+
+    pub fn extend(&mut self, point: &Vec3) {
+        self.min = glm::min2(&self.min, point);
+        self.max = glm::max2(&self.max, point);
+    }
+
+    pub fn merge(&self, other: &Aabb) -> Aabb {
+        Aabb {
+            min: glm::min2(&self.min, &other.min),
+            max: glm::max2(&self.max, &other.max),
+        }
+    }
+
+    /// Box enclosing the transformed box, `matrix` being affine.
+    pub fn transform(&self, matrix: &Mat4) -> Aabb {
+        if self.is_empty() {
+            return *self;
+        }
+        let center = transform_point(matrix, &self.center());
+        let linear: Mat3 = matrix.fixed_view::<3, 3>(0, 0).into();
+        let half_extents = linear.abs() * self.half_extents();
+        Aabb {
+            min: center - half_extents,
+            max: center + half_extents,
+        }
+    }
+
+    pub fn closest_point(&self, point: &Vec3) -> Vec3 {
+        glm::clamp_vec(point, &self.min, &self.max)
+    }
+
+    pub fn contains_point(&self, point: &Vec3) -> bool {
+        (0..3).all(|i| point[i] >= self.min[i] && point[i] <= self.max[i])
+    }
+
+    pub fn intersects_aabb(&self, other: &Aabb) -> bool {
+        (0..3).all(|i| self.min[i] <= other.max[i] && other.min[i] <= self.max[i])
+    }
+
+    pub fn intersects_sphere(&self, sphere: &BoundingSphere) -> bool {
+        !self.is_empty()
+            && glm::distance2(&self.closest_point(&sphere.center), &sphere.center)
+                <= sphere.radius * sphere.radius
+    }
+
+    pub fn intersects_obb(&self, obb: &Obb) -> bool {
+        !self.is_empty() && Obb::from(self).intersects_obb(obb)
+    }
+}
+
+impl From<&Obb> for Aabb {
+    fn from(obb: &Obb) -> Aabb {
+        let half_extents = Vec3::from_fn(|i, _| {
+            (0..3)
+                .map(|k| obb.axes[k][i].abs() * obb.half_extents[k])
+                .sum()
+        });
+        Aabb {
+            min: obb.center - half_extents,
+            max: obb.center + half_extents,
+        }
+    }
+}
+
+impl BoundingSphere {
+    pub fn new(center: Vec3, radius: f32) -> Self {
+        Self { center, radius }
+    }
+
+    /// Ritter's sphere: grown from the most distant pair of extreme points along the axes,
+    /// within a few percent of the smallest one. No points give a zero sphere at the origin.
+    pub fn from_points(points: &[Vec3]) -> Self {
+        if points.is_empty() {
+            return Self::new(Vec3::zeros(), 0.0);
+        }
+        let (mut a, mut b) = (points[0], points[0]);
+        for axis in 0..3 {
+            let (min, max) = points
+                .iter()
+                .fold((points[0], points[0]), |(min, max), &p| {
+                    (
+                        if p[axis] < min[axis] { p } else { min },
+                        if p[axis] > max[axis] { p } else { max },
+                    )
+                });
+            if glm::distance2(&min, &max) > glm::distance2(&a, &b) {
+                (a, b) = (min, max);
+            }
+        }
+        let mut sphere = Self::new((a + b) * 0.5, glm::distance(&a, &b) * 0.5);
+        for p in points {
+            sphere.extend(p);
+        }
+        // padded so that the points stay inside despite the rounding of `extend`
+        sphere.radius += (sphere.radius + sphere.center.abs().max()) * (4.0 * f32::EPSILON);
+        sphere
+    }
+
+    pub fn extend(&mut self, point: &Vec3) {
+        let distance = glm::distance(&self.center, point);
+        if distance > self.radius {
+            let radius = (self.radius + distance) * 0.5;
+            self.center += (point - self.center) * ((radius - self.radius) / distance);
+            self.radius = radius;
+        }
+    }
+
+    pub fn merge(&self, other: &BoundingSphere) -> BoundingSphere {
+        let distance = glm::distance(&self.center, &other.center);
+        if distance + other.radius <= self.radius {
+            return *self;
+        }
+        if distance + self.radius <= other.radius {
+            return *other;
+        }
+        let radius = (distance + self.radius + other.radius) * 0.5;
+        let center =
+            self.center + (other.center - self.center) * ((radius - self.radius) / distance);
+        BoundingSphere { center, radius }
+    }
+
+    /// Sphere enclosing the transformed sphere, `matrix` being affine.
+    pub fn transform(&self, matrix: &Mat4) -> BoundingSphere {
+        let scale = (0..3)
+            .map(|i| matrix.fixed_view::<3, 1>(0, i).norm())
+            .fold(0.0, f32::max);
+        BoundingSphere {
+            center: transform_point(matrix, &self.center),
+            radius: self.radius * scale,
+        }
+    }
+
+    pub fn contains_point(&self, point: &Vec3) -> bool {
+        glm::distance2(&self.center, point) <= self.radius * self.radius
+    }
+
+    pub fn intersects_aabb(&self, aabb: &Aabb) -> bool {
+        aabb.intersects_sphere(self)
+    }
+
+    pub fn intersects_sphere(&self, other: &BoundingSphere) -> bool {
+        let radius = self.radius + other.radius;
+        glm::distance2(&self.center, &other.center) <= radius * radius
+    }
+
+    pub fn intersects_obb(&self, obb: &Obb) -> bool {
+        obb.intersects_sphere(self)
+    }
+}
+
+impl Obb {
+    /// Box along the principal axes of the points, from the eigenvectors of their covariance.
+    /// No points give a zero box at the origin.
+    pub fn from_points(points: &[Vec3]) -> Self {
+        if points.is_empty() {
+            return Self::from(&Aabb::new(Vec3::zeros(), Vec3::zeros()));
+        }
+        let count = points.len() as f32;
+        let mean = points.iter().sum::<Vec3>() / count;
+        let covariance = points.iter().fold(Mat3::zeros(), |sum, p| {
+            let d = p - mean;
+            sum + d * d.transpose()
+        }) / count;
+        let eigen = covariance.symmetric_eigen();
+        let x = glm::normalize(&eigen.eigenvectors.column(0).into_owned());
+        let y = eigen.eigenvectors.column(1).into_owned();
+        let y = glm::normalize(&(y - x * glm::dot(&x, &y)));
+        Self::fit(points, [x, y, glm::cross(&x, &y)])
+    }
+
+    /// Smallest box with the given orthonormal axes enclosing `points`.
+    fn fit(points: &[Vec3], axes: [Vec3; 3]) -> Self {
+        let mut min = Vec3::repeat(f32::INFINITY);
+        let mut max = Vec3::repeat(f32::NEG_INFINITY);
+        let mut reach: f32 = 0.0;
+        for p in points {
+            reach = reach.max(p.abs().max());
+            let local = Vec3::new(
+                glm::dot(p, &axes[0]),
+                glm::dot(p, &axes[1]),
+                glm::dot(p, &axes[2]),
+            );
+            min = glm::min2(&min, &local);
+            max = glm::max2(&max, &local);
+        }
+        let middle = (min + max) * 0.5;
+        // padded so that the points stay inside despite the rounding of `local`
+        let padding = Vec3::repeat(reach * (8.0 * f32::EPSILON));
+        Obb {
+            center: axes[0] * middle.x + axes[1] * middle.y + axes[2] * middle.z,
+            axes,
+            half_extents: (max - min) * 0.5 + padding,
+        }
+    }
+
+    pub fn corners(&self) -> [Vec3; 8] {
+        std::array::from_fn(|i| {
+            let sign = |bit: usize| if i & bit == 0 { -1.0 } else { 1.0 };
+            self.center
+                + self.axes[0] * (self.half_extents.x * sign(1))
+                + self.axes[1] * (self.half_extents.y * sign(2))
+                + self.axes[2] * (self.half_extents.z * sign(4))
+        })
+    }
+
+    pub fn merge(&self, other: &Obb) -> Obb {
+        let mut corners = self.corners().to_vec();
+        corners.extend(other.corners());
+        Obb::from_points(&corners)
+    }
+
+    /// Box enclosing the transformed box, `matrix` being affine. A shear makes the
+    /// transformed axes skewed, the box then follows the first two of them.
+    pub fn transform(&self, matrix: &Mat4) -> Obb {
+        let x = glm::normalize(&transform_vector(matrix, &self.axes[0]));
+        let y = transform_vector(matrix, &self.axes[1]);
+        let y = glm::normalize(&(y - x * glm::dot(&x, &y)));
+        let corners = self.corners().map(|p| transform_point(matrix, &p));
+        Obb::fit(&corners, [x, y, glm::cross(&x, &y)])
+    }
+
+    /// Position of `point` along the axes, relative to the center.
+    fn local(&self, point: &Vec3) -> Vec3 {
+        let d = point - self.center;
+        Vec3::new(
+            glm::dot(&d, &self.axes[0]),
+            glm::dot(&d, &self.axes[1]),
+            glm::dot(&d, &self.axes[2]),
+        )
+    }
+
+    pub fn closest_point(&self, point: &Vec3) -> Vec3 {
+        let local = glm::clamp_vec(
+            &self.local(point),
+            &(-self.half_extents),
+            &self.half_extents,
+        );
+        self.center + self.axes[0] * local.x + self.axes[1] * local.y + self.axes[2] * local.z
+    }
+
+    pub fn contains_point(&self, point: &Vec3) -> bool {
+        let local = self.local(point);
+        (0..3).all(|i| local[i].abs() <= self.half_extents[i])
+    }
+
+    pub fn intersects_aabb(&self, aabb: &Aabb) -> bool {
+        aabb.intersects_obb(self)
+    }
+
+    pub fn intersects_sphere(&self, sphere: &BoundingSphere) -> bool {
+        glm::distance2(&self.closest_point(&sphere.center), &sphere.center)
+            <= sphere.radius * sphere.radius
+    }
+
+    /// Separating axis test over the face normals of both boxes and their cross products.
+    pub fn intersects_obb(&self, other: &Obb) -> bool {
+        // rotation of `other` in the frame of `self`, padded against parallel edges
+        let rotation = Mat3::from_fn(|i, j| glm::dot(&self.axes[i], &other.axes[j]));
+        let padded = rotation.abs().add_scalar(1e-6);
+        let t = self.local(&other.center);
+        let (a, b) = (&self.half_extents, &other.half_extents);
+
+        for i in 0..3 {
+            if t[i].abs() > a[i] + padded.row(i).dot(&b.transpose()) {
+                return false;
+            }
+        }
+        for j in 0..3 {
+            let projected = t.dot(&rotation.column(j));
+            if projected.abs() > padded.column(j).dot(a) + b[j] {
+                return false;
+            }
+        }
+        for i in 0..3 {
+            let (i1, i2) = ((i + 1) % 3, (i + 2) % 3);
+            for j in 0..3 {
+                let (j1, j2) = ((j + 1) % 3, (j + 2) % 3);
+                let ra = a[i1] * padded[(i2, j)] + a[i2] * padded[(i1, j)];
+                let rb = b[j1] * padded[(i, j2)] + b[j2] * padded[(i, j1)];
+                let projected = t[i2] * rotation[(i1, j)] - t[i1] * rotation[(i2, j)];
+                if projected.abs() > ra + rb {
+                    return false;
+                }
+            }
+        }
+        true
+    }
+}
+
+impl From<&Aabb> for Obb {
+    fn from(aabb: &Aabb) -> Obb {
+        Obb {
+            center: aabb.center(),
+            axes: [Vec3::x(), Vec3::y(), Vec3::z()],
+            half_extents: aabb.half_extents(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra_glm as glm;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    use super::{Aabb, BoundingSphere, Obb};
+    use crate::geometry::Vec3;
+
+    fn random_points(rng: &mut StdRng, count: usize, offset: Vec3) -> Vec<Vec3> {
+        // stretched and rotated so that the principal axes are not the world axes
+        let rotation = glm::rotation(0.7, &glm::normalize(&Vec3::new(1.0, 2.0, 3.0)));
+        (0..count)
+            .map(|_| {
+                let p = Vec3::new(
+                    rng.gen_range(-4.0..4.0),
+                    rng.gen_range(-1.0..1.0),
+                    rng.gen_range(-0.2..0.2),
+                );
+                offset + (rotation * p.push(0.0)).xyz()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn bounds_from_points_contain_them() {
+        let mut rng = StdRng::seed_from_u64(21);
+        // far from the origin the rounding of the projections must not leave points out
+        for offset in [Vec3::zeros(), Vec3::new(1e4, -3e4, 2e4)] {
+            for _ in 0..20 {
+                let points = random_points(&mut rng, 100, offset);
+                let aabb = Aabb::from_points(&points);
+                let sphere = BoundingSphere::from_points(&points);
+                let obb = Obb::from_points(&points);
+                for p in &points {
+                    assert!(aabb.contains_point(p));
+                    assert!(sphere.contains_point(p));
+                    assert!(obb.contains_point(p));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn empty_bounds() {
+        let empty = Aabb::from_points(&[]);
+        assert!(empty.is_empty());
+        assert_eq!(empty.surface_area(), 0.0);
+        assert_eq!(empty.transform(&glm::translation(&Vec3::x())), empty);
+        let unit = Aabb::new(-Vec3::repeat(1.0), Vec3::repeat(1.0));
+        assert!(!empty.contains_point(&Vec3::zeros()));
+        assert!(!empty.intersects_aabb(&unit));
+        assert!(!empty.intersects_sphere(&BoundingSphere::new(Vec3::zeros(), 10.0)));
+        assert!(!empty.intersects_obb(&Obb::from(&unit)));
+        assert_eq!(empty.merge(&unit), unit);
+
+        assert_eq!(BoundingSphere::from_points(&[]).radius, 0.0);
+        assert_eq!(Obb::from_points(&[]).half_extents, Vec3::zeros());
+    }
+
+    #[test]
+    fn intersections() {
+        let unit = Aabb::new(-Vec3::repeat(1.0), Vec3::repeat(1.0));
+        let moved = |x: f32| Aabb::new(unit.min + Vec3::x() * x, unit.max + Vec3::x() * x);
+        assert!(unit.intersects_aabb(&moved(1.5)));
+        assert!(unit.intersects_aabb(&moved(2.0)));
+        assert!(!unit.intersects_aabb(&moved(2.5)));
+
+        // the sphere nears the corner along the diagonal
+        let diagonal = glm::normalize(&Vec3::repeat(1.0));
+        let sphere =
+            |distance: f32| BoundingSphere::new(Vec3::repeat(1.0) + diagonal * distance, 1.0);
+        assert!(unit.intersects_sphere(&sphere(0.9)));
+        assert!(!unit.intersects_sphere(&sphere(1.1)));
+        assert!(sphere(0.9).intersects_sphere(&sphere(2.8)));
+        assert!(!sphere(0.9).intersects_sphere(&sphere(3.0)));
+
+        // a cube turned 45 degrees around z reaches sqrt(2) along x
+        let turned = |x: f32| {
+            Obb::from(&unit).transform(
+                &(glm::translation(&(Vec3::x() * x))
+                    * glm::rotation(std::f32::consts::FRAC_PI_4, &Vec3::z())),
+            )
+        };
+        assert!(unit.intersects_obb(&turned(2.3)));
+        assert!(!unit.intersects_obb(&turned(2.5)));
+        assert!(turned(2.3).intersects_aabb(&unit));
+        assert!(turned(0.0).intersects_obb(&turned(2.7)));
+        assert!(!turned(0.0).intersects_obb(&turned(2.9)));
+        assert!(turned(2.3).intersects_sphere(&BoundingSphere::new(Vec3::zeros(), 1.0)));
+        assert!(!turned(2.5).intersects_sphere(&BoundingSphere::new(Vec3::zeros(), 1.0)));
+    }
+
+    #[test]
+    fn transformed_bounds_contain_transformed_points() {
+        let mut rng = StdRng::seed_from_u64(12);
+        let points = random_points(&mut rng, 50, Vec3::new(1.0, 2.0, 3.0));
+        let matrix = glm::translation(&Vec3::new(-3.0, 0.5, 2.0))
+            * glm::rotation(1.1, &glm::normalize(&Vec3::new(0.0, 1.0, 1.0)))
+            * glm::scaling(&Vec3::new(2.0, 0.5, 1.5));
+        let aabb = Aabb::from_points(&points).transform(&matrix);
+        let sphere = BoundingSphere::from_points(&points).transform(&matrix);
+        let obb = Obb::from_points(&points).transform(&matrix);
+        for p in &points {
+            let p = (matrix * p.push(1.0)).xyz();
+            assert!(aabb.contains_point(&p));
+            assert!(sphere.contains_point(&p));
+            assert!(obb.contains_point(&p));
+        }
+    }
+}
@@ -0,0 +1,485 @@
+/*
+* SPDX-License-Identifier: MIT
+*/
+
+use nalgebra_glm as glm;
+
+use super::bounds::Aabb;
+use super::picking::{triangle_positions, TriangleHit};
+use super::primitives::{Mesh, Ray};
+use super::Vec3;
+
+const MAX_LEAF_SIZE: usize = 4;
+const BIN_COUNT: usize = 16;
+/// Cost of visiting a node relative to intersecting a triangle.
+const TRAVERSAL_COST: f32 = 1.0;
+
+#[derive(Clone, Copy, Debug)]
+pub struct NearestPoint {
+    pub triangle: usize,
+    pub point: Vec3,
+    pub distance: f32,
+}
+
+/// Leaves hold `count` triangles from `first` in the triangle order of the hierarchy,
+/// inner nodes have no triangles and their children at `first` and `first + 1`, which
+/// is never the root. The root of an empty hierarchy is an empty leaf.
+#[derive(Clone, Copy, Debug)]
+struct Node {
+    bounds: Aabb,
+    first: u32,
+    count: u32,
+}
+
+impl Node {
+    fn is_leaf(&self) -> bool {
+        self.count > 0 || self.first == 0
+    }
+}
+
+/// Bounding volume hierarchy over the triangles of a mesh, built with the surface area
+/// heuristic. The mesh is not kept, queries take the mesh the hierarchy was built from,
+/// possibly with moved vertices after a [`Bvh::refit`].
+#[derive(Clone, Debug)]
+pub struct Bvh {
+    nodes: Vec<Node>,
+    triangles: Vec<u32>,
+}
+
+fn triangle_bounds(mesh: &Mesh, index: usize) -> Aabb {
+    Aabb::from_points(&triangle_positions(mesh, index))
+}
+
+impl Bvh {
+    pub fn new(mesh: &Mesh) -> Self {
+        let count = mesh.indices.len() / 3;
+        let bounds: Vec<Aabb> = (0..count).map(|i| triangle_bounds(mesh, i)).collect();
+        let centroids: Vec<Vec3> = bounds.iter().map(|b| b.center()).collect();
+        let mut bvh = Bvh {
+            nodes: vec![Node {
+                bounds: Aabb::empty(),
+                first: 0,
+                count: count as u32,
+            }],
+            triangles: (0..count as u32).collect(),
+        };
+        if count > 0 {
+            bvh.split(0, &bounds, &centroids);
+        }
+        bvh
+    }
+
+    /// Splits the leaf `index` at the cheapest of the binned planes along the three axes,
+    /// unless keeping the leaf is cheaper.
+    fn split(&mut self, index: usize, bounds: &[Aabb], centroids: &[Vec3]) {
+        let Node { first, count, .. } = self.nodes[index];
+        let range = first as usize..(first + count) as usize;
+        let node_bounds = self.triangles[range.clone()]
+            .iter()
+            .fold(Aabb::empty(), |b, &t| b.merge(&bounds[t as usize]));
+        self.nodes[index].bounds = node_bounds;
+        if range.len() <= 1 {
+            return;
+        }
+
+        let centroid_bounds = Aabb::from_points(
+            &self.triangles[range.clone()]
+                .iter()
+                .map(|&t| centroids[t as usize])
+                .collect::<Vec<_>>(),
+        );
+        let bin = |axis: usize, centroid: &Vec3| {
+            let extent = centroid_bounds.max[axis] - centroid_bounds.min[axis];
+            let relative = (centroid[axis] - centroid_bounds.min[axis]) / extent;
+            ((relative * BIN_COUNT as f32) as usize).min(BIN_COUNT - 1)
+        };
+
+        // (cost, axis, last bin on the left side)
+        let mut best: Option<(f32, usize, usize)> = None;
+        for axis in 0..3 {
+            if centroid_bounds.max[axis] <= centroid_bounds.min[axis] {
+                continue;
+            }
+            let mut bins = [(Aabb::empty(), 0usize); BIN_COUNT];
+            for &t in &self.triangles[range.clone()] {
+                let b = &mut bins[bin(axis, &centroids[t as usize])];
+                b.0 = b.0.merge(&bounds[t as usize]);
+                b.1 += 1;
+            }
+            let mut right_costs = [0.0; BIN_COUNT];
+            let (mut right, mut right_count) = (Aabb::empty(), 0);
+            for i in (1..BIN_COUNT).rev() {
+                right = right.merge(&bins[i].0);
+                right_count += bins[i].1;
+                right_costs[i] = right.surface_area() * right_count as f32;
+            }
+            let (mut left, mut left_count) = (Aabb::empty(), 0);
+            for i in 0..BIN_COUNT - 1 {
+                left = left.merge(&bins[i].0);
+                left_count += bins[i].1;
+                if left_count == 0 || left_count == range.len() {
+                    continue;
+                }
+                let cost = left.surface_area() * left_count as f32 + right_costs[i + 1];
+                if best.is_none_or(|(best, _, _)| cost < best) {
+                    best = Some((cost, axis, i));
+                }
+            }
+        }
+
+        let Some((cost, axis, last_left)) = best else {
+            return;
+        };
+        let area = node_bounds.surface_area().max(f32::MIN_POSITIVE);
+        let split_cost = TRAVERSAL_COST + cost / area;
+        if range.len() <= MAX_LEAF_SIZE && split_cost >= range.len() as f32 {
+            return;
+        }
+
+        let slice = &mut self.triangles[range.clone()];
+        let mut left_count = 0;
+        for i in 0..slice.len() {
+            if bin(axis, &centroids[slice[i] as usize]) <= last_left {
+                slice.swap(i, left_count);
+                left_count += 1;
+            }
+        }
+
+        let children = self.nodes.len();
+        self.nodes[index] = Node {
+            bounds: node_bounds,
+            first: children as u32,
+            count: 0,
+        };
+        self.nodes.push(Node {
+            bounds: Aabb::empty(),
+            first,
+            count: left_count as u32,
+        });
+        self.nodes.push(Node {
+            bounds: Aabb::empty(),
+            first: first + left_count as u32,
+            count: count - left_count as u32,
+        });
+        self.split(children, bounds, centroids);
+        self.split(children + 1, bounds, centroids);
+    }
+
+    pub fn bounds(&self) -> Aabb {
+        self.nodes[0].bounds
+    }
+
+    /// Updates the bounds after the vertices of `mesh` moved, keeping the tree. Cheap, but
+    /// the queries slow down as the triangles drift away from where they were at build time.
+    pub fn refit(&mut self, mesh: &Mesh) {
+        // children always come after their parent
+        for index in (0..self.nodes.len()).rev() {
+            let node = self.nodes[index];
+            self.nodes[index].bounds = if node.is_leaf() {
+                self.leaf_triangles(&node)
+                    .iter()
+                    .fold(Aabb::empty(), |b, &t| {
+                        b.merge(&triangle_bounds(mesh, t as usize))
+                    })
+            } else {
+                let first = node.first as usize;
+                self.nodes[first]
+                    .bounds
+                    .merge(&self.nodes[first + 1].bounds)
+            };
+        }
+    }
+
+    fn leaf_triangles(&self, node: &Node) -> &[u32] {
+        &self.triangles[node.first as usize..(node.first + node.count) as usize]
+    }
+
+    /// Closest triangle hit by `ray` within `max_distance`.
+    pub fn closest_hit(&self, mesh: &Mesh, ray: &Ray, max_distance: f32) -> Option<TriangleHit> {
+        self.traverse(mesh, ray, max_distance, false)
+    }
+
+    /// Any triangle hit by `ray` within `max_distance`, enough for occlusion queries.
+    pub fn any_hit(&self, mesh: &Mesh, ray: &Ray, max_distance: f32) -> Option<TriangleHit> {
+        self.traverse(mesh, ray, max_distance, true)
+    }
+
+    fn traverse(
+        &self,
+        mesh: &Mesh,
+        ray: &Ray,
+        max_distance: f32,
+        any: bool,
+    ) -> Option<TriangleHit> {
+        let entry = |node: &Node| ray.intersect_aabb(&node.bounds).map(|(near, _)| near);
+        // (triangle, distance, barycentric) of the closest hit so far
+        let mut closest: Option<(usize, f32, Vec3)> = None;
+        let mut limit = max_distance;
+        let mut stack = Vec::with_capacity(64);
+        if let Some(t) = entry(&self.nodes[0]) {
+            stack.push((0, t));
+        }
+        'traversal: while let Some((index, near)) = stack.pop() {
+            if near > limit {
+                continue;
+            }
+            let node = &self.nodes[index];
+            if node.is_leaf() {
+                for &t in self.leaf_triangles(node) {
+                    let hit = ray.intersect_triangle(&triangle_positions(mesh, t as usize));
+                    if let Some((distance, barycentric)) = hit.filter(|&(d, _)| d <= limit) {
+                        limit = distance;
+                        closest = Some((t as usize, distance, barycentric));
+                        if any {
+                            break 'traversal;
+                        }
+                    }
+                }
+                continue;
+            }
+            // the nearest child is visited first
+            let first = node.first as usize;
+            let mut children: Vec<(usize, f32)> = [first, first + 1]
+                .into_iter()
+                .filter_map(|c| entry(&self.nodes[c]).map(|t| (c, t)))
+                .filter(|&(_, t)| t <= limit)
+                .collect();
+            children.sort_by(|a, b| b.1.total_cmp(&a.1));
+            stack.extend(children);
+        }
+        closest.map(|(triangle, t, barycentric)| TriangleHit::new(mesh, triangle, t, barycentric))
+    }
+
+    /// Triangles overlapping `aabb`.
+    pub fn overlapping(&self, mesh: &Mesh, aabb: &Aabb) -> Vec<usize> {
+        let mut found = Vec::new();
+        let mut stack = vec![0];
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+            if !node.bounds.intersects_aabb(aabb) {
+                continue;
+            }
+            if node.is_leaf() {
+                found.extend(
+                    self.leaf_triangles(node)
+                        .iter()
+                        .map(|&t| t as usize)
+                        .filter(|&t| triangle_overlaps_box(&triangle_positions(mesh, t), aabb)),
+                );
+            } else {
+                stack.extend([node.first as usize, node.first as usize + 1]);
+            }
+        }
+        found
+    }
+
+    /// Point of the mesh closest to `point`, no farther than `max_distance`.
+    pub fn nearest_point(
+        &self,
+        mesh: &Mesh,
+        point: &Vec3,
+        max_distance: f32,
+    ) -> Option<NearestPoint> {
+        let box_distance = |bounds: &Aabb| {
+            if bounds.is_empty() {
+                f32::INFINITY
+            } else {
+                glm::distance(&bounds.closest_point(point), point)
+            }
+        };
+        let mut nearest: Option<NearestPoint> = None;
+        let mut limit = max_distance;
+        let mut stack = vec![(0, box_distance(&self.nodes[0].bounds))];
+        while let Some((index, distance)) = stack.pop() {
+            if distance > limit {
+                continue;
+            }
+            let node = &self.nodes[index];
+            if node.is_leaf() {
+                for &t in self.leaf_triangles(node) {
+                    let closest =
+                        closest_point_on_triangle(point, &triangle_positions(mesh, t as usize));
+                    let distance = glm::distance(&closest, point);
+                    if distance <= limit {
+                        limit = distance;
+                        nearest = Some(NearestPoint {
+                            triangle: t as usize,
+                            point: closest,
+                            distance,
+                        });
+                    }
+                }
+                continue;
+            }
+            let first = node.first as usize;
+            let mut children = [first, first + 1].map(|c| (c, box_distance(&self.nodes[c].bounds)));
+            children.sort_by(|a, b| b.1.total_cmp(&a.1));
+            stack.extend(children);
+        }
+        nearest
+    }
+}
+
+/// Separating axis test of a triangle against a box: the box faces, the triangle plane
+/// and the cross products of their edges.
+fn triangle_overlaps_box(triangle: &[Vec3; 3], aabb: &Aabb) -> bool {
+    if aabb.is_empty() {
+        return false;
+    }
+    let (center, half) = (aabb.center(), aabb.half_extents());
+    let v = triangle.map(|p| p - center);
+    let edges = [v[1] - v[0], v[2] - v[1], v[0] - v[2]];
+    let separates = |axis: Vec3| {
+        let projected = v.map(|p| glm::dot(&p, &axis));
+        let radius = glm::dot(&half, &axis.abs());
+        let min = projected.iter().copied().fold(f32::INFINITY, f32::min);
+        let max = projected.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+        min > radius || max < -radius
+    };
+    let box_axes = [Vec3::x(), Vec3::y(), Vec3::z()];
+    !(box_axes.iter().any(|&axis| separates(axis))
+        || separates(glm::cross(&edges[0], &edges[1]))
+        || edges.iter().any(|edge| {
+            box_axes
+                .iter()
+                .any(|axis| separates(glm::cross(axis, edge)))
+        }))
+}
+
+/// Closest point of the triangle, by the region of the triangle `point` projects to
+/// (Ericson, Real-Time Collision Detection 5.1.5).
+fn closest_point_on_triangle(point: &Vec3, [a, b, c]: &[Vec3; 3]) -> Vec3 {
+    let (ab, ac, ap) = (b - a, c - a, point - a);
+    let (d1, d2) = (glm::dot(&ab, &ap), glm::dot(&ac, &ap));
+    if d1 <= 0.0 && d2 <= 0.0 {
+        return *a;
+    }
+    let bp = point - b;
+    let (d3, d4) = (glm::dot(&ab, &bp), glm::dot(&ac, &bp));
+    if d3 >= 0.0 && d4 <= d3 {
+        return *b;
+    }
+    let vc = d1 * d4 - d3 * d2;
+    if vc <= 0.0 && d1 >= 0.0 && d3 <= 0.0 {
+        return a + ab * (d1 / (d1 - d3));
+    }
+    let cp = point - c;
+    let (d5, d6) = (glm::dot(&ab, &cp), glm::dot(&ac, &cp));
+    if d6 >= 0.0 && d5 <= d6 {
+        return *c;
+    }
+    let vb = d5 * d2 - d1 * d6;
+    if vb <= 0.0 && d2 >= 0.0 && d6 <= 0.0 {
+        return a + ac * (d2 / (d2 - d6));
+    }
+    let va = d3 * d6 - d5 * d4;
+    if va <= 0.0 && d4 - d3 >= 0.0 && d5 - d6 >= 0.0 {
+        return b + (c - b) * ((d4 - d3) / ((d4 - d3) + (d5 - d6)));
+    }
+    let denom = 1.0 / (va + vb + vc);
+    if !denom.is_finite() {
+        // degenerate triangle, closest of its edges
+        return [(a, b), (b, c), (c, a)]
+            .map(|(p, q)| {
+                let d = q - p;
+                let t = (glm::dot(&(point - p), &d) / d.norm_squared().max(f32::MIN_POSITIVE))
+                    .clamp(0.0, 1.0);
+                p + d * t
+            })
+            .into_iter()
+            .min_by(|x, y| glm::distance2(x, point).total_cmp(&glm::distance2(y, point)))
+            .unwrap_or(*a);
+    }
+    a + ab * (vb * denom) + ac * (vc * denom)
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra_glm as glm;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    use super::{closest_point_on_triangle, triangle_overlaps_box, Bvh};
+    use crate::geometry::bounds::Aabb;
+    use crate::geometry::picking::triangle_positions;
+    use crate::geometry::primitives::{Mesh, Ray};
+    use crate::geometry::shapes::torus;
+    use crate::geometry::Vec3;
+
+    fn random_point(rng: &mut StdRng, extent: f32) -> Vec3 {
+        Vec3::from_fn(|_, _| rng.gen_range(-extent..extent))
+    }
+
+    fn triangles(mesh: &Mesh) -> impl Iterator<Item = (usize, [Vec3; 3])> + '_ {
+        (0..mesh.indices.len() / 3).map(|t| (t, triangle_positions(mesh, t)))
+    }
+
+    #[test]
+    fn ray_queries_match_brute_force() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let mut mesh = torus(2.0, 0.5, 32, 16);
+        let mut bvh = Bvh::new(&mesh);
+        for round in 0..2 {
+            if round == 1 {
+                for vertex in &mut mesh.vertices {
+                    vertex.position = vertex.position.component_mul(&Vec3::new(1.5, 0.5, 1.0));
+                }
+                bvh.refit(&mesh);
+            }
+            for _ in 0..500 {
+                let origin = random_point(&mut rng, 4.0);
+                let ray = Ray::new(origin, random_point(&mut rng, 1.0) - origin * 0.2);
+                let expected = ray.intersect_mesh(&mesh);
+                let hit = bvh.closest_hit(&mesh, &ray, f32::INFINITY);
+                assert_eq!(hit.is_some(), expected.is_some());
+                if let (Some(hit), Some(expected)) = (hit, expected) {
+                    assert!((hit.t - expected.t).abs() <= 1e-4 * expected.t.max(1.0));
+                    assert!(bvh.any_hit(&mesh, &ray, expected.t * 0.99).is_none());
+                    let any = bvh.any_hit(&mesh, &ray, f32::INFINITY).unwrap();
+                    assert!(any.t >= hit.t);
+                } else {
+                    assert!(bvh.any_hit(&mesh, &ray, f32::INFINITY).is_none());
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn nearest_point_and_overlap_match_brute_force() {
+        let mut rng = StdRng::seed_from_u64(2);
+        let mesh = torus(2.0, 0.5, 32, 16);
+        let bvh = Bvh::new(&mesh);
+        for _ in 0..200 {
+            let point = random_point(&mut rng, 4.0);
+            let expected = triangles(&mesh)
+                .map(|(_, t)| glm::distance(&closest_point_on_triangle(&point, &t), &point))
+                .fold(f32::INFINITY, f32::min);
+            let nearest = bvh.nearest_point(&mesh, &point, f32::INFINITY).unwrap();
+            assert!((nearest.distance - expected).abs() <= 1e-5);
+            assert!(bvh.nearest_point(&mesh, &point, expected * 0.99).is_none());
+
+            let aabb = Aabb::new(point, point + random_point(&mut rng, 1.0).abs());
+            let mut found = bvh.overlapping(&mesh, &aabb);
+            found.sort_unstable();
+            let expected: Vec<usize> = triangles(&mesh)
+                .filter(|(_, t)| triangle_overlaps_box(t, &aabb))
+                .map(|(index, _)| index)
+                .collect();
+            assert_eq!(found, expected);
+        }
+    }
+
+    #[test]
+    fn empty_mesh() {
+        let mesh = Mesh {
+            vertices: Vec::new(),
+            indices: Vec::new(),
+        };
+        let bvh = Bvh::new(&mesh);
+        let ray = Ray::new(Vec3::zeros(), Vec3::new(0.0, 0.0, 1.0));
+        assert!(bvh.closest_hit(&mesh, &ray, f32::INFINITY).is_none());
+        assert!(bvh
+            .nearest_point(&mesh, &Vec3::zeros(), f32::INFINITY)
+            .is_none());
+    }
+}
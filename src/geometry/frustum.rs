@@ -0,0 +1,219 @@
+/*
+* SPDX-License-Identifier: MIT
+*/
+
+use super::bounds::{Aabb, BoundingSphere, Obb};
+use super::primitives::Plane;
+use super::{Mat4, Vec3};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Containment {
+    Inside,
+    Outside,
+    Intersecting,
+}
+
+/// Volume seen by a camera, bounded by planes with normals pointing inwards.
+/// Classifications are conservative: bounds near a corner of the frustum can be reported
+/// as intersecting while being outside, never the other way round.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Frustum {
+    /// Left, right, bottom, top, near and far planes.
+    pub planes: [Plane; 6],
+}
+
+impl Frustum {
+    /// Planes of the clip space of `view_projection` (Gribb-Hartmann), the Vulkan one:
+    /// `x` and `y` in `[-w, w]` and `z` in `[0, w]`. The frustum of a camera is
+    /// `Frustum::from_matrix(&(projection * view))`.
+    pub fn from_matrix(view_projection: &Mat4) -> Self {
+        let row = |i: usize| view_projection.row(i).transpose();
+        let (x, y, z, w) = (row(0), row(1), row(2), row(3));
+        Self {
+            planes: [w + x, w - x, w + y, w - y, z, w - z].map(Plane::from_coefficients),
+        }
+    }
+
+    pub fn contains_point(&self, point: &Vec3) -> bool {
+        self.planes
+            .iter()
+            .all(|plane| plane.signed_distance(point) >= 0.0)
+    }
+
+    /// Classifies a volume from the distance of its center to every plane and its
+    /// extent along the plane normal.
+    fn classify(&self, center: &Vec3, radius: impl Fn(&Plane) -> f32) -> Containment {
+        let mut containment = Containment::Inside;
+        for plane in &self.planes {
+            let distance = plane.signed_distance(center);
+            let radius = radius(plane);
+            if distance < -radius {
+                return Containment::Outside;
+            }
+            if distance < radius {
+                containment = Containment::Intersecting;
+            }
+        }
+        containment
+    }
+
+    pub fn classify_aabb(&self, aabb: &Aabb) -> Containment {
+        if aabb.is_empty() {
+            return Containment::Outside;
+        }
+        let half_extents = aabb.half_extents();
+        self.classify(&aabb.center(), |plane| {
+            half_extents.dot(&plane.normal.abs())
+        })
+    }
+
+    pub fn classify_sphere(&self, sphere: &BoundingSphere) -> Containment {
+        self.classify(&sphere.center, |_| sphere.radius)
+    }
+
+    pub fn classify_obb(&self, obb: &Obb) -> Containment {
+        self.classify(&obb.center, |plane| {
+            (0..3)
+                .map(|k| obb.half_extents[k] * plane.normal.dot(&obb.axes[k]).abs())
+                .sum()
+        })
+    }
+
+    pub fn classify_aabbs(&self, aabbs: &[Aabb]) -> Vec<Containment> {
+        aabbs.iter().map(|aabb| self.classify_aabb(aabb)).collect()
+    }
+
+    pub fn classify_spheres(&self, spheres: &[BoundingSphere]) -> Vec<Containment> {
+        spheres
+            .iter()
+            .map(|sphere| self.classify_sphere(sphere))
+            .collect()
+    }
+
+    pub fn classify_obbs(&self, obbs: &[Obb]) -> Vec<Containment> {
+        obbs.iter().map(|obb| self.classify_obb(obb)).collect()
+    }
+
+    /// Indices of the boxes at least partly inside the frustum.
+    pub fn cull_aabbs(&self, aabbs: &[Aabb]) -> Vec<usize> {
+        visible(aabbs, |aabb| self.classify_aabb(aabb))
+    }
+
+    /// Indices of the spheres at least partly inside the frustum.
+    pub fn cull_spheres(&self, spheres: &[BoundingSphere]) -> Vec<usize> {
+        visible(spheres, |sphere| self.classify_sphere(sphere))
+    }
+
+    /// Indices of the boxes at least partly inside the frustum.
+    pub fn cull_obbs(&self, obbs: &[Obb]) -> Vec<usize> {
+        visible(obbs, |obb| self.classify_obb(obb))
+    }
+}
+
+fn visible<T>(bounds: &[T], classify: impl Fn(&T) -> Containment) -> Vec<usize> {
+    bounds
+        .iter()
+        .enumerate()
+        .filter(|(_, b)| classify(b) != Containment::Outside)
+        .map(|(i, _)| i)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra_glm as glm;
+
+    use super::{Containment, Frustum};
+    use crate::geometry::bounds::{Aabb, BoundingSphere, Obb};
+    use crate::geometry::Vec3;
+
+    /// Camera at `z = 10` looking at the origin, seeing `z` from 9 to -10.
+    fn frustum() -> Frustum {
+        let view = glm::look_at(
+            &Vec3::new(0.0, 0.0, 10.0),
+            &Vec3::zeros(),
+            &Vec3::new(0.0, 1.0, 0.0),
+        );
+        let mut projection = glm::perspective_rh_zo(1.0, 1.0, 1.0, 20.0);
+        projection[(1, 1)] *= -1.0;
+        Frustum::from_matrix(&(projection * view))
+    }
+
+    fn cube(center: Vec3, half_size: f32) -> Aabb {
+        Aabb::new(
+            center - Vec3::repeat(half_size),
+            center + Vec3::repeat(half_size),
+        )
+    }
+
+    #[test]
+    fn classifies_known_bounds() {
+        let frustum = frustum();
+        assert!(frustum.contains_point(&Vec3::zeros()));
+        assert!(!frustum.contains_point(&Vec3::new(0.0, 0.0, 9.5)));
+        assert!(!frustum.contains_point(&Vec3::new(0.0, 0.0, -10.5)));
+
+        let boxes = [
+            cube(Vec3::zeros(), 1.0),
+            // behind the camera, beyond the far plane and off to the side
+            cube(Vec3::new(0.0, 0.0, 12.0), 1.0),
+            cube(Vec3::new(0.0, 0.0, -15.0), 1.0),
+            cube(Vec3::new(20.0, 0.0, 0.0), 1.0),
+            // across the near plane, the far plane and a side plane
+            cube(Vec3::new(0.0, 0.0, 9.0), 0.5),
+            cube(Vec3::new(0.0, 0.0, -10.0), 0.5),
+            cube(Vec3::new(5.5, 0.0, 0.0), 1.0),
+            Aabb::empty(),
+        ];
+        use Containment::*;
+        let expected = [
+            Inside,
+            Outside,
+            Outside,
+            Outside,
+            Intersecting,
+            Intersecting,
+            Intersecting,
+            Outside,
+        ];
+        assert_eq!(frustum.classify_aabbs(&boxes), expected);
+        assert_eq!(frustum.cull_aabbs(&boxes), [0, 4, 5, 6]);
+
+        let spheres: Vec<BoundingSphere> = boxes[..7]
+            .iter()
+            .map(|aabb| BoundingSphere::new(aabb.center(), aabb.half_extents().x))
+            .collect();
+        assert_eq!(frustum.classify_spheres(&spheres), expected[..7]);
+
+        let rotation = glm::rotation(0.6, &glm::normalize(&Vec3::new(1.0, 1.0, 0.0)));
+        let obbs: Vec<Obb> = boxes[..7]
+            .iter()
+            .map(|aabb| {
+                let center = glm::translation(&aabb.center());
+                Obb::from(&cube(Vec3::zeros(), aabb.half_extents().x))
+                    .transform(&(center * rotation))
+            })
+            .collect();
+        assert_eq!(frustum.classify_obbs(&obbs), expected[..7]);
+    }
+
+    #[test]
+    fn classification_agrees_with_corners() {
+        let frustum = frustum();
+        for x in -12..=12 {
+            for z in -14..=12 {
+                let aabb = cube(Vec3::new(x as f32, 0.5, z as f32), 0.75);
+                let inside = aabb
+                    .corners()
+                    .iter()
+                    .filter(|corner| frustum.contains_point(corner))
+                    .count();
+                match frustum.classify_aabb(&aabb) {
+                    Containment::Inside => assert_eq!(inside, 8),
+                    Containment::Outside => assert_eq!(inside, 0),
+                    Containment::Intersecting => assert!(inside < 8),
+                }
+            }
+        }
+    }
+}
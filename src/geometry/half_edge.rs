@@ -0,0 +1,555 @@
+/*
+* SPDX-License-Identifier: MIT
+*/
+
+use std::collections::HashMap;
+use std::fmt;
+
+use nalgebra_glm as glm;
+
+use super::primitives::{Mesh, Vertex};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum HalfEdgeError {
+    InvalidIndex {
+        face: usize,
+        index: u32,
+    },
+    DegenerateFace(usize),
+    /// The edge is shared by more than two faces or by two faces of opposite orientation.
+    NonManifoldEdge {
+        from: u32,
+        to: u32,
+    },
+    /// Several fans of faces meet at the vertex.
+    NonManifoldVertex(u32),
+    BoundaryEdge,
+    NotTriangle,
+    /// The operation would create a non manifold or degenerate mesh.
+    TopologyViolation,
+}
+
+impl fmt::Display for HalfEdgeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HalfEdgeError::InvalidIndex { face, index } => {
+                write!(f, "face {} refers to the missing vertex {}", face, index)
+            }
+            HalfEdgeError::DegenerateFace(face) => {
+                write!(f, "face {} has less than 3 distinct vertices", face)
+            }
+            HalfEdgeError::NonManifoldEdge { from, to } => {
+                write!(f, "edge from {} to {} is not manifold", from, to)
+            }
+            HalfEdgeError::NonManifoldVertex(vertex) => {
+                write!(f, "vertex {} is not manifold", vertex)
+            }
+            HalfEdgeError::BoundaryEdge => write!(f, "operation not supported on a boundary edge"),
+            HalfEdgeError::NotTriangle => write!(f, "operation requires triangular faces"),
+            HalfEdgeError::TopologyViolation => {
+                write!(f, "operation would break the mesh topology")
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+struct HalfEdge {
+    origin: u32,
+    twin: usize,
+    next: usize,
+    prev: usize,
+    /// `None` for the half-edges running along a boundary.
+    face: Option<usize>,
+    removed: bool,
+}
+
+/// Polygon mesh with half-edge connectivity, every edge being made of two opposite
+/// half-edges and the boundaries being loops of half-edges without a face.
+#[derive(Clone, Debug)]
+pub struct HalfEdgeMesh {
+    vertices: Vec<Vertex>,
+    /// An outgoing half-edge of every vertex, on the boundary if there is one.
+    outgoing: Vec<Option<usize>>,
+    half_edges: Vec<HalfEdge>,
+    /// A half-edge of every face.
+    faces: Vec<Option<usize>>,
+}
+
+impl HalfEdgeMesh {
+    pub fn from_mesh(mesh: &Mesh) -> Result<Self, HalfEdgeError> {
+        let faces: Vec<Vec<u32>> = mesh.indices.chunks_exact(3).map(|t| t.to_vec()).collect();
+        Self::from_polygons(mesh.vertices.clone(), &faces)
+    }
+
+    /// Builds the mesh from polygons given as counter-clockwise loops of vertex indices.
+    pub fn from_polygons(vertices: Vec<Vertex>, faces: &[Vec<u32>]) -> Result<Self, HalfEdgeError> {
+        let mut half_edges: Vec<HalfEdge> = Vec::new();
+        let mut edges: HashMap<(u32, u32), usize> = HashMap::new();
+        let mut face_edges = Vec::with_capacity(faces.len());
+
+        for (face, polygon) in faces.iter().enumerate() {
+            if let Some(&index) = polygon.iter().find(|&&i| i as usize >= vertices.len()) {
+                return Err(HalfEdgeError::InvalidIndex { face, index });
+            }
+            let mut distinct = polygon.clone();
+            distinct.sort_unstable();
+            distinct.dedup();
+            if polygon.len() < 3 || distinct.len() != polygon.len() {
+                return Err(HalfEdgeError::DegenerateFace(face));
+            }
+
+            let first = half_edges.len();
+            let count = polygon.len();
+            for (k, &from) in polygon.iter().enumerate() {
+                let to = polygon[(k + 1) % count];
+                if edges.insert((from, to), first + k).is_some() {
+                    return Err(HalfEdgeError::NonManifoldEdge { from, to });
+                }
+                half_edges.push(HalfEdge {
+                    origin: from,
+                    twin: usize::MAX,
+                    next: first + (k + 1) % count,
+                    prev: first + (k + count - 1) % count,
+                    face: Some(face),
+                    removed: false,
+                });
+            }
+            face_edges.push(Some(first));
+        }
+
+        // pair the half-edges, creating boundary ones for the unpaired
+        let mut boundary_from: HashMap<u32, usize> = HashMap::new();
+        for h in 0..half_edges.len() {
+            if half_edges[h].twin != usize::MAX {
+                continue;
+            }
+            let from = half_edges[h].origin;
+            let to = half_edges[half_edges[h].next].origin;
+            match edges.get(&(to, from)) {
+                Some(&twin) => {
+                    half_edges[h].twin = twin;
+                    half_edges[twin].twin = h;
+                }
+                None => {
+                    let boundary = half_edges.len();
+                    half_edges.push(HalfEdge {
+                        origin: to,
+                        twin: h,
+                        next: usize::MAX,
+                        prev: usize::MAX,
+                        face: None,
+                        removed: false,
+                    });
+                    half_edges[h].twin = boundary;
+                    if boundary_from.insert(to, boundary).is_some() {
+                        return Err(HalfEdgeError::NonManifoldVertex(to));
+                    }
+                }
+            }
+        }
+        let boundaries: Vec<usize> = boundary_from.values().copied().collect();
+        for boundary in boundaries {
+            let to = half_edges[half_edges[boundary].twin].origin;
+            let next = boundary_from[&to];
+            half_edges[boundary].next = next;
+            half_edges[next].prev = boundary;
+        }
+
+        let mut outgoing = vec![None; vertices.len()];
+        let mut outgoing_count = vec![0; vertices.len()];
+        for (h, half_edge) in half_edges.iter().enumerate() {
+            let vertex = half_edge.origin as usize;
+            outgoing_count[vertex] += 1;
+            if outgoing[vertex].is_none() || half_edge.face.is_none() {
+                outgoing[vertex] = Some(h);
+            }
+        }
+
+        let mesh = Self {
+            vertices,
+            outgoing,
+            half_edges,
+            faces: face_edges,
+        };
+        for (vertex, &count) in outgoing_count.iter().enumerate() {
+            if mesh.outgoing(vertex as u32).count() != count {
+                return Err(HalfEdgeError::NonManifoldVertex(vertex as u32));
+            }
+        }
+        Ok(mesh)
+    }
+
+    /// Triangulates the faces as fans, vertices without faces are dropped.
+    pub fn to_mesh(&self) -> Mesh {
+        let mut remap = vec![u32::MAX; self.vertices.len()];
+        let mut vertices = Vec::new();
+        for (vertex, outgoing) in self.outgoing.iter().enumerate() {
+            if outgoing.is_some() {
+                remap[vertex] = vertices.len() as u32;
+                vertices.push(self.vertices[vertex]);
+            }
+        }
+        let mut indices = Vec::new();
+        for face in self.faces() {
+            let polygon = self.face_vertices(face);
+            for k in 1..polygon.len() - 1 {
+                indices.extend([polygon[0], polygon[k], polygon[k + 1]].map(|v| remap[v as usize]));
+            }
+        }
+        Mesh { vertices, indices }
+    }
+
+    pub fn vertex(&self, vertex: u32) -> &Vertex {
+        &self.vertices[vertex as usize]
+    }
+
+    pub fn vertex_mut(&mut self, vertex: u32) -> &mut Vertex {
+        &mut self.vertices[vertex as usize]
+    }
+
+    /// Indices of the vertices still connected to a face.
+    pub fn vertices(&self) -> impl Iterator<Item = u32> + '_ {
+        (0..self.vertices.len() as u32).filter(|&v| self.outgoing[v as usize].is_some())
+    }
+
+    pub fn faces(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..self.faces.len()).filter(|&f| self.faces[f].is_some())
+    }
+
+    pub fn face_count(&self) -> usize {
+        self.faces().count()
+    }
+
+    pub fn origin(&self, half_edge: usize) -> u32 {
+        self.half_edges[half_edge].origin
+    }
+
+    pub fn target(&self, half_edge: usize) -> u32 {
+        self.origin(self.twin(half_edge))
+    }
+
+    pub fn twin(&self, half_edge: usize) -> usize {
+        self.half_edges[half_edge].twin
+    }
+
+    pub fn next(&self, half_edge: usize) -> usize {
+        self.half_edges[half_edge].next
+    }
+
+    pub fn prev(&self, half_edge: usize) -> usize {
+        self.half_edges[half_edge].prev
+    }
+
+    pub fn face(&self, half_edge: usize) -> Option<usize> {
+        self.half_edges[half_edge].face
+    }
+
+    pub fn is_boundary_edge(&self, half_edge: usize) -> bool {
+        self.face(half_edge).is_none() || self.face(self.twin(half_edge)).is_none()
+    }
+
+    pub fn is_boundary_vertex(&self, vertex: u32) -> bool {
+        self.outgoing(vertex).any(|h| self.face(h).is_none())
+    }
+
+    /// Half-edge going from `from` to `to`.
+    pub fn find_half_edge(&self, from: u32, to: u32) -> Option<usize> {
+        self.outgoing(from).find(|&h| self.target(h) == to)
+    }
+
+    /// Half-edges leaving `vertex`, starting from the boundary if it lies on one.
+    pub fn outgoing(&self, vertex: u32) -> impl Iterator<Item = usize> + '_ {
+        let start = self.outgoing[vertex as usize];
+        let mut current = start;
+        std::iter::from_fn(move || {
+            let half_edge = current?;
+            let next = self.next(self.twin(half_edge));
+            current = if Some(next) == start {
+                None
+            } else {
+                Some(next)
+            };
+            Some(half_edge)
+        })
+    }
+
+    /// Vertices sharing an edge with `vertex`.
+    pub fn one_ring(&self, vertex: u32) -> Vec<u32> {
+        self.outgoing(vertex).map(|h| self.target(h)).collect()
+    }
+
+    pub fn face_half_edges(&self, face: usize) -> impl Iterator<Item = usize> + '_ {
+        let start = self.faces[face];
+        let mut current = start;
+        std::iter::from_fn(move || {
+            let half_edge = current?;
+            let next = self.next(half_edge);
+            current = if Some(next) == start {
+                None
+            } else {
+                Some(next)
+            };
+            Some(half_edge)
+        })
+    }
+
+    pub fn face_vertices(&self, face: usize) -> Vec<u32> {
+        self.face_half_edges(face).map(|h| self.origin(h)).collect()
+    }
+
+    /// Faces sharing an edge with `face`.
+    pub fn face_neighbours(&self, face: usize) -> Vec<usize> {
+        self.face_half_edges(face)
+            .filter_map(|h| self.face(self.twin(h)))
+            .collect()
+    }
+
+    /// Vertex loops of the boundaries, following the boundary half-edges.
+    pub fn boundary_loops(&self) -> Vec<Vec<u32>> {
+        let mut visited = vec![false; self.half_edges.len()];
+        let mut loops = Vec::new();
+        for (start, half_edge) in self.half_edges.iter().enumerate() {
+            if half_edge.removed || half_edge.face.is_some() || visited[start] {
+                continue;
+            }
+            let mut boundary = Vec::new();
+            let mut current = start;
+            while !visited[current] {
+                visited[current] = true;
+                boundary.push(self.origin(current));
+                current = self.next(current);
+            }
+            loops.push(boundary);
+        }
+        loops
+    }
+
+    fn is_triangle(&self, face: usize) -> bool {
+        self.face_half_edges(face).count() == 3
+    }
+
+    fn update_outgoing(&mut self, vertex: u32, candidate: usize) {
+        let current = self.outgoing[vertex as usize];
+        if current.is_none_or(|h| self.half_edges[h].removed || self.origin(h) != vertex)
+            || self.face(candidate).is_none()
+        {
+            self.outgoing[vertex as usize] = Some(candidate);
+        }
+    }
+
+    /// Rotates the edge of `half_edge` inside the two triangles sharing it.
+    pub fn flip_edge(&mut self, half_edge: usize) -> Result<(), HalfEdgeError> {
+        let h = half_edge;
+        let t = self.twin(h);
+        let (Some(f1), Some(f2)) = (self.face(h), self.face(t)) else {
+            return Err(HalfEdgeError::BoundaryEdge);
+        };
+        if !self.is_triangle(f1) || !self.is_triangle(f2) {
+            return Err(HalfEdgeError::NotTriangle);
+        }
+        let (h1, h2) = (self.next(h), self.prev(h));
+        let (t1, t2) = (self.next(t), self.prev(t));
+        let (a, b) = (self.origin(h), self.origin(t));
+        let (c, d) = (self.origin(h2), self.origin(t2));
+        if c == d || self.find_half_edge(c, d).is_some() {
+            return Err(HalfEdgeError::TopologyViolation);
+        }
+
+        // f1 becomes d -> c -> a, f2 becomes c -> d -> b
+        self.link(&[h, h2, t1], f1);
+        self.link(&[t, t2, h1], f2);
+        self.half_edges[h].origin = d;
+        self.half_edges[t].origin = c;
+        if self.outgoing[a as usize] == Some(h) {
+            self.outgoing[a as usize] = Some(t1);
+        }
+        if self.outgoing[b as usize] == Some(t) {
+            self.outgoing[b as usize] = Some(h1);
+        }
+        Ok(())
+    }
+
+    fn link(&mut self, cycle: &[usize], face: usize) {
+        for (k, &h) in cycle.iter().enumerate() {
+            self.half_edges[h].next = cycle[(k + 1) % cycle.len()];
+            self.half_edges[h].prev = cycle[(k + cycle.len() - 1) % cycle.len()];
+            self.half_edges[h].face = Some(face);
+        }
+        self.faces[face] = Some(cycle[0]);
+    }
+
+    fn push_half_edge(&mut self, origin: u32, face: Option<usize>) -> usize {
+        self.half_edges.push(HalfEdge {
+            origin,
+            twin: usize::MAX,
+            next: usize::MAX,
+            prev: usize::MAX,
+            face,
+            removed: false,
+        });
+        self.half_edges.len() - 1
+    }
+
+    /// Inserts a vertex at `t` along `half_edge`, interpolating the attributes, and splits
+    /// the triangles on both sides of the edge. Returns the new vertex.
+    pub fn split_edge(&mut self, half_edge: usize, t: f32) -> u32 {
+        let h = half_edge;
+        let twin = self.twin(h);
+        let (a, b) = (self.origin(h), self.origin(twin));
+        let (start, end) = (self.vertices[a as usize], self.vertices[b as usize]);
+        let normal = glm::lerp(&start.normal, &end.normal, t);
+        self.vertices.push(Vertex {
+            position: glm::lerp(&start.position, &end.position, t),
+            normal: if normal.norm_squared() > 0.0 {
+                glm::normalize(&normal)
+            } else {
+                start.normal
+            },
+            uv: glm::lerp(&start.uv, &end.uv, t),
+        });
+        let m = self.vertices.len() as u32 - 1;
+        self.outgoing.push(None);
+
+        // h: a -> m, h_end: m -> b, twin: b -> m, twin_end: m -> a
+        let h_end = self.push_half_edge(m, self.face(h));
+        let twin_end = self.push_half_edge(m, self.face(twin));
+        for (first, second) in [(h, h_end), (twin, twin_end)] {
+            let next = self.next(first);
+            self.half_edges[second].next = next;
+            self.half_edges[next].prev = second;
+            self.half_edges[first].next = second;
+            self.half_edges[second].prev = first;
+        }
+        self.half_edges[h].twin = twin_end;
+        self.half_edges[twin_end].twin = h;
+        self.half_edges[twin].twin = h_end;
+        self.half_edges[h_end].twin = twin;
+        self.outgoing[m as usize] = Some(h_end);
+        self.update_outgoing(m, twin_end);
+
+        for (first, second) in [(h, h_end), (twin, twin_end)] {
+            if let Some(face) = self.face(second) {
+                if self.face_half_edges(face).count() == 4 {
+                    // connect the new vertex to the opposite corner of the former triangle
+                    let opposite = self.next(self.next(second));
+                    self.split_face(face, second, opposite);
+                    debug_assert_eq!(self.next(opposite), first);
+                }
+            }
+        }
+        m
+    }
+
+    /// Splits `face` with a new edge between the origins of `from` and `to`.
+    fn split_face(&mut self, face: usize, from: usize, to: usize) {
+        let (from_vertex, to_vertex) = (self.origin(from), self.origin(to));
+        let new_face = self.faces.len();
+        self.faces.push(None);
+        let closing = self.push_half_edge(to_vertex, Some(face));
+        let opening = self.push_half_edge(from_vertex, Some(new_face));
+        self.half_edges[closing].twin = opening;
+        self.half_edges[opening].twin = closing;
+
+        let mut first = vec![from];
+        let mut current = self.next(from);
+        while current != to {
+            first.push(current);
+            current = self.next(current);
+        }
+        first.push(closing);
+        let mut second = vec![to];
+        let mut current = self.next(to);
+        while current != from {
+            second.push(current);
+            current = self.next(current);
+        }
+        second.push(opening);
+        self.link(&first, face);
+        self.link(&second, new_face);
+    }
+
+    /// Merges the two ends of `half_edge` into its origin, moved to the middle of the edge,
+    /// removing the triangles on both sides. Returns the remaining vertex.
+    pub fn collapse_edge(&mut self, half_edge: usize) -> Result<u32, HalfEdgeError> {
+        let h = half_edge;
+        let t = self.twin(h);
+        let (a, b) = (self.origin(h), self.origin(t));
+        let sides: Vec<usize> = [h, t]
+            .into_iter()
+            .filter(|&e| self.face(e).is_some())
+            .collect();
+        if sides
+            .iter()
+            .any(|&e| !self.is_triangle(self.face(e).unwrap()))
+        {
+            return Err(HalfEdgeError::NotTriangle);
+        }
+        // link condition: the only common neighbours are the corners of the removed triangles
+        let ring_b = self.one_ring(b);
+        let common = self
+            .one_ring(a)
+            .iter()
+            .filter(|v| ring_b.contains(v))
+            .count();
+        if common != sides.len()
+            || (sides.len() == 2 && self.is_boundary_vertex(a) && self.is_boundary_vertex(b))
+            || sides.iter().any(|&e| {
+                self.is_boundary_edge(self.next(e)) && self.is_boundary_edge(self.prev(e))
+            })
+        {
+            return Err(HalfEdgeError::TopologyViolation);
+        }
+
+        let from_b: Vec<usize> = self.outgoing(b).collect();
+        let mut survivors: Vec<usize> = self.outgoing(a).chain(from_b.iter().copied()).collect();
+        for side in [h, t] {
+            let (next, prev) = (self.next(side), self.prev(side));
+            let mut removed = vec![side];
+            if let Some(face) = self.face(side) {
+                // the two other edges of the triangle become one
+                let (outer_next, outer_prev) = (self.twin(next), self.twin(prev));
+                self.half_edges[outer_next].twin = outer_prev;
+                self.half_edges[outer_prev].twin = outer_next;
+                let corner = self.origin(prev);
+                if self.outgoing[corner as usize] == Some(prev) {
+                    self.outgoing[corner as usize] = Some(outer_next);
+                }
+                self.update_outgoing(corner, outer_next);
+                self.faces[face] = None;
+                removed.extend([next, prev]);
+            } else {
+                self.half_edges[prev].next = next;
+                self.half_edges[next].prev = prev;
+            }
+            for e in removed {
+                self.half_edges[e].removed = true;
+            }
+        }
+        for e in from_b {
+            if !self.half_edges[e].removed {
+                self.half_edges[e].origin = a;
+            }
+        }
+
+        survivors.retain(|&e| !self.half_edges[e].removed);
+        self.outgoing[a as usize] = None;
+        self.outgoing[b as usize] = None;
+        for e in survivors {
+            self.update_outgoing(a, e);
+        }
+
+        let (start, end) = (self.vertices[a as usize], self.vertices[b as usize]);
+        let normal = start.normal + end.normal;
+        self.vertices[a as usize] = Vertex {
+            position: (start.position + end.position) * 0.5,
+            normal: if normal.norm_squared() > 0.0 {
+                glm::normalize(&normal)
+            } else {
+                start.normal
+            },
+            uv: (start.uv + end.uv) * 0.5,
+        };
+        Ok(a)
+    }
+}
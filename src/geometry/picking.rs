@@ -0,0 +1,293 @@
+/*
+* SPDX-License-Identifier: MIT
+*/
+
+use nalgebra_glm as glm;
+
+use super::bounds::{Aabb, BoundingSphere};
+use super::primitives::{Mesh, Plane, Ray, Vertex};
+use super::{Mat4, Vec2, Vec3, Vec4};
+
+#[derive(Clone, Copy, Debug)]
+pub struct TriangleHit {
+    pub triangle: usize,
+    /// Distance along the ray.
+    pub t: f32,
+    /// Weights of the three corners of the triangle at the hit point.
+    pub barycentric: Vec3,
+    /// Attributes of the corners interpolated at the hit point.
+    pub vertex: Vertex,
+}
+
+impl TriangleHit {
+    pub(crate) fn new(mesh: &Mesh, triangle: usize, t: f32, barycentric: Vec3) -> Self {
+        let corners = triangle_indices(mesh, triangle).map(|i| mesh.vertices[i as usize]);
+        let interpolate = |attribute: fn(&Vertex) -> Vec3| {
+            (0..3).fold(Vec3::zeros(), |sum, k| {
+                sum + attribute(&corners[k]) * barycentric[k]
+            })
+        };
+        let normal = interpolate(|v| v.normal);
+        let uv = (0..3).fold(Vec2::zeros(), |sum, k| sum + corners[k].uv * barycentric[k]);
+        Self {
+            triangle,
+            t,
+            barycentric,
+            vertex: Vertex {
+                position: interpolate(|v| v.position),
+                normal: if normal.norm_squared() > 0.0 {
+                    glm::normalize(&normal)
+                } else {
+                    normal
+                },
+                uv,
+            },
+        }
+    }
+}
+
+fn triangle_indices(mesh: &Mesh, triangle: usize) -> [u32; 3] {
+    let t = &mesh.indices[triangle * 3..triangle * 3 + 3];
+    [t[0], t[1], t[2]]
+}
+
+pub(crate) fn triangle_positions(mesh: &Mesh, triangle: usize) -> [Vec3; 3] {
+    triangle_indices(mesh, triangle).map(|i| mesh.vertices[i as usize].position)
+}
+
+impl Ray {
+    /// Ray from the camera through the point `pixel` of a viewport of `size` pixels, the
+    /// y axis pointing down as in Vulkan framebuffers. It starts on the near plane, at depth 0
+    /// of the Vulkan clip space. `None` if `projection * view` is not invertible.
+    pub fn from_screen(pixel: Vec2, size: Vec2, view: &Mat4, projection: &Mat4) -> Option<Ray> {
+        let inverse = (projection * view).try_inverse()?;
+        let ndc = pixel.component_div(&size) * 2.0 - Vec2::repeat(1.0);
+        let unproject = |depth: f32| {
+            let p = inverse * Vec4::new(ndc.x, ndc.y, depth, 1.0);
+            p.xyz() / p.w
+        };
+        let (near, far) = (unproject(0.0), unproject(1.0));
+        Some(Ray::new(near, far - near))
+    }
+
+    /// Distance to the triangle and barycentric weights of its corners at the hit point,
+    /// by the Möller-Trumbore algorithm. Both sides of the triangle are hit.
+    pub fn intersect_triangle(&self, [a, b, c]: &[Vec3; 3]) -> Option<(f32, Vec3)> {
+        let (ab, ac) = (b - a, c - a);
+        let p = glm::cross(&self.direction, &ac);
+        let determinant = glm::dot(&ab, &p);
+        if determinant.abs() <= f32::EPSILON * ab.norm() * ac.norm() * self.direction.norm() {
+            return None;
+        }
+        let inverse = 1.0 / determinant;
+        let s = self.origin - a;
+        let u = glm::dot(&s, &p) * inverse;
+        if !(0.0..=1.0).contains(&u) {
+            return None;
+        }
+        let q = glm::cross(&s, &ab);
+        let v = glm::dot(&self.direction, &q) * inverse;
+        if v < 0.0 || u + v > 1.0 {
+            return None;
+        }
+        let t = glm::dot(&ac, &q) * inverse;
+        (t >= 0.0).then(|| (t, Vec3::new(1.0 - u - v, u, v)))
+    }
+
+    /// Distances at which the ray enters and leaves the box by the slab test, the entry
+    /// being zero from inside. An empty box is never hit.
+    pub fn intersect_aabb(&self, aabb: &Aabb) -> Option<(f32, f32)> {
+        if aabb.is_empty() {
+            return None;
+        }
+        let (mut near, mut far) = (0.0_f32, f32::INFINITY);
+        for axis in 0..3 {
+            let inverse = 1.0 / self.direction[axis];
+            let t0 = (aabb.min[axis] - self.origin[axis]) * inverse;
+            let t1 = (aabb.max[axis] - self.origin[axis]) * inverse;
+            if t0.is_nan() || t1.is_nan() {
+                // the ray runs in the plane of a face
+                continue;
+            }
+            near = near.max(t0.min(t1));
+            far = far.min(t0.max(t1));
+        }
+        (near <= far).then_some((near, far))
+    }
+
+    /// Distance at which the ray enters the sphere, or leaves it from inside.
+    pub fn intersect_sphere(&self, sphere: &BoundingSphere) -> Option<f32> {
+        let offset = self.origin - sphere.center;
+        let a = self.direction.norm_squared();
+        let half_b = glm::dot(&offset, &self.direction);
+        let c = offset.norm_squared() - sphere.radius * sphere.radius;
+        let discriminant = half_b * half_b - a * c;
+        if discriminant < 0.0 {
+            return None;
+        }
+        let root = discriminant.sqrt();
+        [(-half_b - root) / a, (-half_b + root) / a]
+            .into_iter()
+            .find(|&t| t >= 0.0)
+    }
+
+    /// Distance to the plane, from either side.
+    pub fn intersect_plane(&self, plane: &Plane) -> Option<f32> {
+        let speed = glm::dot(&plane.normal, &self.direction);
+        if speed == 0.0 {
+            return None;
+        }
+        let t = -plane.signed_distance(&self.origin) / speed;
+        (t >= 0.0).then_some(t)
+    }
+
+    /// Closest triangle of `mesh` hit by the ray, testing every triangle. A
+    /// [`Bvh`](super::bvh::Bvh) answers the same query for large meshes.
+    pub fn intersect_mesh(&self, mesh: &Mesh) -> Option<TriangleHit> {
+        (0..mesh.indices.len() / 3)
+            .filter_map(|triangle| {
+                self.intersect_triangle(&triangle_positions(mesh, triangle))
+                    .map(|(t, barycentric)| (triangle, t, barycentric))
+            })
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(triangle, t, barycentric)| TriangleHit::new(mesh, triangle, t, barycentric))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra_glm as glm;
+
+    use crate::geometry::bounds::{Aabb, BoundingSphere};
+    use crate::geometry::primitives::{Mesh, Plane, Ray, Vertex};
+    use crate::geometry::{Vec2, Vec3};
+
+    fn assert_near(a: f32, b: f32) {
+        assert!((a - b).abs() < 1e-5, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn triangle() {
+        let triangle = [Vec3::zeros(), Vec3::x(), Vec3::y()];
+        let down = |x: f32, y: f32| Ray::new(Vec3::new(x, y, 2.0), -Vec3::z());
+        let (t, barycentric) = down(0.25, 0.5).intersect_triangle(&triangle).unwrap();
+        assert_near(t, 2.0);
+        assert!(glm::distance(&barycentric, &Vec3::new(0.25, 0.25, 0.5)) < 1e-5);
+        // from the back side, outside the edges, behind the origin and along the plane
+        let up = Ray::new(Vec3::new(0.25, 0.25, -1.0), Vec3::z());
+        assert!(up.intersect_triangle(&triangle).is_some());
+        assert!(down(0.6, 0.6).intersect_triangle(&triangle).is_none());
+        assert!(down(-0.1, 0.5).intersect_triangle(&triangle).is_none());
+        assert!(Ray::new(Vec3::new(0.25, 0.25, 1.0), Vec3::z())
+            .intersect_triangle(&triangle)
+            .is_none());
+        assert!(Ray::new(Vec3::new(-1.0, 0.25, 0.0), Vec3::x())
+            .intersect_triangle(&triangle)
+            .is_none());
+    }
+
+    #[test]
+    fn aabb() {
+        let unit = Aabb::new(-Vec3::repeat(1.0), Vec3::repeat(1.0));
+        let ray = Ray::new(Vec3::new(-3.0, 0.5, 0.0), Vec3::x());
+        assert_eq!(ray.intersect_aabb(&unit), Some((2.0, 4.0)));
+        assert_eq!(
+            Ray::new(Vec3::zeros(), Vec3::y()).intersect_aabb(&unit),
+            Some((0.0, 1.0))
+        );
+        assert!(Ray::new(Vec3::new(-3.0, 1.5, 0.0), Vec3::x())
+            .intersect_aabb(&unit)
+            .is_none());
+        assert!(Ray::new(Vec3::new(3.0, 0.0, 0.0), Vec3::x())
+            .intersect_aabb(&unit)
+            .is_none());
+        // running in the plane of a face
+        assert_eq!(
+            Ray::new(Vec3::new(-3.0, 1.0, 0.0), Vec3::x()).intersect_aabb(&unit),
+            Some((2.0, 4.0))
+        );
+        assert!(ray.intersect_aabb(&Aabb::empty()).is_none());
+    }
+
+    #[test]
+    fn sphere_and_plane() {
+        let sphere = BoundingSphere::new(Vec3::new(0.0, 0.0, -5.0), 2.0);
+        let ray = Ray::new(Vec3::zeros(), -Vec3::z());
+        assert_near(ray.intersect_sphere(&sphere).unwrap(), 3.0);
+        let inside = Ray::new(sphere.center, Vec3::x());
+        assert_near(inside.intersect_sphere(&sphere).unwrap(), 2.0);
+        assert!(Ray::new(Vec3::zeros(), Vec3::z())
+            .intersect_sphere(&sphere)
+            .is_none());
+        assert!(Ray::new(Vec3::new(2.5, 0.0, 0.0), -Vec3::z())
+            .intersect_sphere(&sphere)
+            .is_none());
+
+        let plane = Plane::new(Vec3::new(0.0, 0.0, -4.0), Vec3::z());
+        assert_near(ray.intersect_plane(&plane).unwrap(), 4.0);
+        let below = Ray::new(Vec3::new(0.0, 0.0, -6.0), Vec3::new(1.0, 0.0, 1.0));
+        assert_near(below.intersect_plane(&plane).unwrap(), 2.0 * 2.0_f32.sqrt());
+        assert!(Ray::new(Vec3::zeros(), Vec3::z())
+            .intersect_plane(&plane)
+            .is_none());
+        assert!(Ray::new(Vec3::zeros(), Vec3::x())
+            .intersect_plane(&plane)
+            .is_none());
+    }
+
+    #[test]
+    fn mesh_hit_interpolates_attributes() {
+        let vertex = |x: f32, y: f32, z: f32| Vertex {
+            position: Vec3::new(x, y, z),
+            normal: Vec3::z(),
+            uv: Vec2::new(x, y),
+        };
+        // two stacked quads, the ray from above hits the upper one
+        let mesh = Mesh {
+            vertices: vec![
+                vertex(0.0, 0.0, 0.0),
+                vertex(1.0, 0.0, 0.0),
+                vertex(1.0, 1.0, 0.0),
+                vertex(0.0, 1.0, 0.0),
+                vertex(0.0, 0.0, 1.0),
+                vertex(1.0, 0.0, 1.0),
+                vertex(1.0, 1.0, 1.0),
+                vertex(0.0, 1.0, 1.0),
+            ],
+            indices: vec![0, 1, 2, 0, 2, 3, 4, 5, 6, 4, 6, 7],
+        };
+        let hit = Ray::new(Vec3::new(0.25, 0.75, 3.0), -Vec3::z())
+            .intersect_mesh(&mesh)
+            .unwrap();
+        assert_eq!(hit.triangle, 3);
+        assert_near(hit.t, 2.0);
+        assert!(glm::distance(&hit.vertex.position, &Vec3::new(0.25, 0.75, 1.0)) < 1e-5);
+        assert!(glm::distance(&hit.vertex.uv, &Vec2::new(0.25, 0.75)) < 1e-5);
+        assert_eq!(hit.vertex.normal, Vec3::z());
+        assert!(Ray::new(Vec3::new(2.0, 0.5, 3.0), -Vec3::z())
+            .intersect_mesh(&mesh)
+            .is_none());
+    }
+
+    #[test]
+    fn from_screen() {
+        let view = glm::look_at(
+            &Vec3::new(0.0, 0.0, 10.0),
+            &Vec3::zeros(),
+            &Vec3::new(0.0, 1.0, 0.0),
+        );
+        let mut projection = glm::perspective_rh_zo(800.0 / 600.0, 1.0, 1.0, 20.0);
+        projection[(1, 1)] *= -1.0;
+        let size = Vec2::new(800.0, 600.0);
+
+        let center = Ray::from_screen(size * 0.5, size, &view, &projection).unwrap();
+        assert!(glm::distance(&center.origin, &Vec3::new(0.0, 0.0, 9.0)) < 1e-4);
+        assert!(glm::distance(&center.direction, &-Vec3::z()) < 1e-5);
+        // the top left pixel looks up and to the left
+        let corner = Ray::from_screen(Vec2::zeros(), size, &view, &projection).unwrap();
+        assert!(corner.direction.x < 0.0 && corner.direction.y > 0.0);
+        assert_near(corner.direction.x / corner.direction.y, -800.0 / 600.0);
+
+        assert!(Ray::from_screen(size * 0.5, size, &view, &glm::Mat4::zeros()).is_none());
+    }
+}
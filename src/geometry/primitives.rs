@@ -2,28 +2,97 @@
 * SPDX-License-Identifier: MIT
 */
 
+use nalgebra_glm as glm;
+
 use super::Vec2;
 use super::Vec3;
+use super::Vec4;
 
 #[repr(C)]
+#[derive(Clone, Copy, Debug)]
 pub struct Line {
     pub start: Vec3,
     pub end: Vec3,
 }
 
+#[derive(Clone, Copy, Debug)]
+pub struct Ray {
+    pub origin: Vec3,
+    pub direction: Vec3,
+}
+
+impl Ray {
+    /// The direction is normalized so that ray parameters are distances from the origin.
+    pub fn new(origin: Vec3, direction: Vec3) -> Self {
+        Self {
+            origin,
+            direction: glm::normalize(&direction),
+        }
+    }
+
+    pub fn at(&self, t: f32) -> Vec3 {
+        self.origin + self.direction * t
+    }
+}
+
+/// Points `p` with `dot(normal, p) + distance == 0`, `normal` being a unit vector.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Plane {
+    pub normal: Vec3,
+    pub distance: f32,
+}
+
+impl Plane {
+    pub fn new(point: Vec3, normal: Vec3) -> Self {
+        let normal = glm::normalize(&normal);
+        Self {
+            normal,
+            distance: -glm::dot(&normal, &point),
+        }
+    }
+
+    /// Plane `a * x + b * y + c * z + d == 0` of the coefficients `(a, b, c, d)`, normalized.
+    pub fn from_coefficients(coefficients: Vec4) -> Self {
+        let length = coefficients.xyz().norm();
+        Self {
+            normal: coefficients.xyz() / length,
+            distance: coefficients.w / length,
+        }
+    }
+
+    /// Positive on the side the normal points to.
+    pub fn signed_distance(&self, point: &Vec3) -> f32 {
+        glm::dot(&self.normal, point) + self.distance
+    }
+}
+
 #[repr(C)]
+#[derive(Clone, Copy, Debug)]
 pub struct SimpleVertex {
     pub position: Vec3,
     pub normal: Vec3,
 }
 
 #[repr(C)]
+#[derive(Clone, Copy, Debug)]
 pub struct Vertex {
     pub position: Vec3,
     pub normal: Vec3,
     pub uv: Vec2,
 }
 
+/// Vertex for normal mapping, `tangent.w` is the sign of the bitangent
+/// `cross(normal, tangent.xyz) * tangent.w`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct TangentVertex {
+    pub position: Vec3,
+    pub normal: Vec3,
+    pub uv: Vec2,
+    pub tangent: Vec4,
+}
+
+#[derive(Clone, Debug)]
 pub struct PolyLine {
     pub points: Vec<Vec3>,
     pub line_strip: bool
@@ -41,7 +110,24 @@ pub struct SimpleMesh {
     pub vertices: Vec<Vertex>,
 }
 
+#[derive(Clone, Debug)]
 pub struct Mesh {
     pub vertices: Vec<Vertex>,
     pub indices: Vec<u32>,
 }
+
+impl Mesh {
+    /// Moves the vertices and triangles of `other` into this mesh.
+    pub fn append(&mut self, other: Mesh) {
+        let offset = self.vertices.len() as u32;
+        self.vertices.extend(other.vertices);
+        self.indices
+            .extend(other.indices.into_iter().map(|index| index + offset));
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct TangentMesh {
+    pub vertices: Vec<TangentVertex>,
+    pub indices: Vec<u32>,
+}
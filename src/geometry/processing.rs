@@ -0,0 +1,280 @@
+/*
+* SPDX-License-Identifier: MIT
+*/
+
+use std::collections::HashMap;
+
+use nalgebra_glm as glm;
+
+use super::primitives::{Mesh, TangentMesh, TangentVertex, Vertex};
+use super::{Vec3, Vec4};
+
+/// How the faces around a vertex contribute to its smooth normal.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum NormalWeighting {
+    /// Faces are weighted by the angle of their corner at the vertex.
+    Angle,
+    /// Faces are weighted by their area.
+    Area,
+}
+
+type Cell = (i64, i64, i64);
+
+fn cell(position: &Vec3, size: f32) -> Cell {
+    let p = position / size;
+    (p.x.floor() as i64, p.y.floor() as i64, p.z.floor() as i64)
+}
+
+/// Angle of the corner at `a` of the triangle `a`, `b`, `c`.
+fn corner_angle(a: &Vec3, b: &Vec3, c: &Vec3) -> f32 {
+    let (ab, ac) = (b - a, c - a);
+    if ab.norm_squared() == 0.0 || ac.norm_squared() == 0.0 {
+        return 0.0;
+    }
+    glm::angle(&ab, &ac)
+}
+
+/// Removes the component of `v` along the unit vector `normal`.
+fn project(v: &Vec3, normal: &Vec3) -> Vec3 {
+    v - normal * glm::dot(normal, v)
+}
+
+fn normalize_or_zero(v: &Vec3) -> Vec3 {
+    if v.norm_squared() > 0.0 {
+        glm::normalize(v)
+    } else {
+        glm::zero()
+    }
+}
+
+/// Groups the vertices closer than `tolerance` for which `matches` holds, returning
+/// the first vertex of every group and the group of every vertex.
+pub(crate) fn merge_vertices(
+    vertices: &[Vertex],
+    tolerance: f32,
+    matches: impl Fn(&Vertex, &Vertex) -> bool,
+) -> (Vec<Vertex>, Vec<u32>) {
+    let size = tolerance.max(f32::EPSILON);
+    let mut cells: HashMap<Cell, Vec<u32>> = HashMap::new();
+    let mut merged = Vec::new();
+    let mut remap = Vec::with_capacity(vertices.len());
+    for vertex in vertices {
+        let (x, y, z) = cell(&vertex.position, size);
+        let mut found = None;
+        'search: for dx in -1..=1 {
+            for dy in -1..=1 {
+                for dz in -1..=1 {
+                    let Some(candidates) = cells.get(&(x + dx, y + dy, z + dz)) else {
+                        continue;
+                    };
+                    for &candidate in candidates {
+                        let other: &Vertex = &merged[candidate as usize];
+                        if glm::distance(&other.position, &vertex.position) <= tolerance
+                            && matches(other, vertex)
+                        {
+                            found = Some(candidate);
+                            break 'search;
+                        }
+                    }
+                }
+            }
+        }
+        let index = found.unwrap_or_else(|| {
+            merged.push(*vertex);
+            let index = merged.len() as u32 - 1;
+            cells.entry((x, y, z)).or_default().push(index);
+            index
+        });
+        remap.push(index);
+    }
+    (merged, remap)
+}
+
+impl Mesh {
+    /// Merges the vertices whose positions are closer than `tolerance` and rebuilds
+    /// the indices, dropping the triangles that collapse.
+    /// The merged vertex keeps the position and uv of the first vertex of its group and
+    /// the average of their normals, so that seams between patches stay smooth.
+    pub fn weld(&mut self, tolerance: f32) {
+        self.weld_by(tolerance, |_, _| true);
+    }
+
+    /// Like [`Mesh::weld`] but keeps the uv seams, only merging vertices whose uv
+    /// are also closer than `tolerance`.
+    pub fn weld_matching_uv(&mut self, tolerance: f32) {
+        self.weld_by(tolerance, |a, b| glm::distance(&a.uv, &b.uv) <= tolerance);
+    }
+
+    fn weld_by(&mut self, tolerance: f32, matches: impl Fn(&Vertex, &Vertex) -> bool) {
+        let (mut vertices, remap) = merge_vertices(&self.vertices, tolerance, matches);
+        let mut normals = vec![Vec3::zeros(); vertices.len()];
+        for (vertex, &index) in self.vertices.iter().zip(&remap) {
+            normals[index as usize] += vertex.normal;
+        }
+        for (vertex, normal) in vertices.iter_mut().zip(normals) {
+            if normal.norm_squared() > 0.0 {
+                vertex.normal = glm::normalize(&normal);
+            }
+        }
+        let mut indices = Vec::with_capacity(self.indices.len());
+        for triangle in self.indices.chunks_exact(3) {
+            let [a, b, c] = [0, 1, 2].map(|k| remap[triangle[k] as usize]);
+            if a != b && b != c && c != a {
+                indices.extend([a, b, c]);
+            }
+        }
+        self.vertices = vertices;
+        self.indices = indices;
+    }
+
+    /// Recomputes smooth per-vertex normals.
+    /// Faces meeting at an angle wider than `crease_angle` (in radians) do not share
+    /// normals, the vertices on such hard edges are duplicated; `π` never splits.
+    pub fn compute_normals(&mut self, weighting: NormalWeighting, crease_angle: f32) {
+        let face_normals: Vec<Vec3> = self
+            .indices
+            .chunks_exact(3)
+            .map(|triangle| {
+                let [a, b, c] = [0, 1, 2].map(|k| self.vertices[triangle[k] as usize].position);
+                glm::cross(&(b - a), &(c - a))
+            })
+            .collect();
+        let unit_normals: Vec<Vec3> = face_normals
+            .iter()
+            .map(|n| {
+                if n.norm_squared() > 0.0 {
+                    glm::normalize(n)
+                } else {
+                    glm::zero()
+                }
+            })
+            .collect();
+
+        // corners of every vertex with their weighted face normal
+        let mut corners: Vec<Vec<(usize, Vec3)>> = vec![Vec::new(); self.vertices.len()];
+        for (corner, &index) in self.indices.iter().enumerate() {
+            let face = corner / 3;
+            let weighted = match weighting {
+                NormalWeighting::Area => face_normals[face],
+                NormalWeighting::Angle => {
+                    let base = face * 3;
+                    let position =
+                        |k: usize| self.vertices[self.indices[base + k] as usize].position;
+                    let k = corner - base;
+                    let angle =
+                        corner_angle(&position(k), &position((k + 1) % 3), &position((k + 2) % 3));
+                    unit_normals[face] * angle
+                }
+            };
+            corners[index as usize].push((corner, weighted));
+        }
+
+        let cos_crease = crease_angle.cos();
+        for (vertex, incident) in corners.iter().enumerate() {
+            // normals already used at this vertex and the vertex holding each of them
+            let mut split: Vec<(Vec3, u32)> = Vec::new();
+            for &(corner, _) in incident {
+                let face_normal = unit_normals[corner / 3];
+                let degenerate = face_normal.norm_squared() == 0.0;
+                let sum: Vec3 = incident
+                    .iter()
+                    .filter(|(other, _)| {
+                        degenerate || glm::dot(&face_normal, &unit_normals[other / 3]) >= cos_crease
+                    })
+                    .map(|(_, weighted)| weighted)
+                    .sum();
+                let normal = if sum.norm_squared() > 0.0 {
+                    glm::normalize(&sum)
+                } else {
+                    self.vertices[vertex].normal
+                };
+
+                let index = match split
+                    .iter()
+                    .find(|(other, _)| glm::dot(other, &normal) > 1.0 - 1e-5)
+                {
+                    Some(&(_, index)) => index,
+                    None => {
+                        let index = if split.is_empty() {
+                            vertex as u32
+                        } else {
+                            self.vertices.push(self.vertices[vertex]);
+                            self.vertices.len() as u32 - 1
+                        };
+                        self.vertices[index as usize].normal = normal;
+                        split.push((normal, index));
+                        index
+                    }
+                };
+                self.indices[corner] = index;
+            }
+        }
+    }
+
+    /// Builds per-vertex tangents following MikkTSpace: face tangents are projected
+    /// on the tangent plane of each corner and averaged with angle weights, corners
+    /// whose uv orientation differs (mirrored uv) get their own vertex.
+    pub fn generate_tangents(&self) -> TangentMesh {
+        let mut groups: HashMap<(u32, bool), usize> = HashMap::new();
+        let mut sums: Vec<Vec3> = Vec::new();
+        let mut vertices: Vec<TangentVertex> = Vec::new();
+        let mut indices = Vec::with_capacity(self.indices.len());
+
+        for triangle in self.indices.chunks_exact(3) {
+            let corners: [&Vertex; 3] = [0, 1, 2].map(|k| &self.vertices[triangle[k] as usize]);
+            let [p0, p1, p2] = corners.map(|c| c.position);
+            let [t0, t1, t2] = corners.map(|c| c.uv);
+            let (d1, d2) = (t1 - t0, t2 - t0);
+            let signed_area = d1.x * d2.y - d2.x * d1.y;
+            let orientation = signed_area >= 0.0;
+            // tangent of the face scaled by the inverse of the uv area
+            let face_tangent = if signed_area != 0.0 {
+                ((p1 - p0) * d2.y - (p2 - p0) * d1.y) * signed_area.signum()
+            } else {
+                glm::zero()
+            };
+
+            for k in 0..3 {
+                let vertex = corners[k];
+                let normal = normalize_or_zero(&vertex.normal);
+                let (previous, next) =
+                    (corners[(k + 2) % 3].position, corners[(k + 1) % 3].position);
+                let edge_next = normalize_or_zero(&project(&(next - vertex.position), &normal));
+                let edge_previous =
+                    normalize_or_zero(&project(&(previous - vertex.position), &normal));
+                let angle = glm::dot(&edge_next, &edge_previous).clamp(-1.0, 1.0).acos();
+                let tangent = normalize_or_zero(&project(&face_tangent, &normal));
+
+                let group = *groups.entry((triangle[k], orientation)).or_insert_with(|| {
+                    vertices.push(TangentVertex {
+                        position: vertex.position,
+                        normal: vertex.normal,
+                        uv: vertex.uv,
+                        tangent: Vec4::new(0.0, 0.0, 0.0, if orientation { 1.0 } else { -1.0 }),
+                    });
+                    sums.push(glm::zero());
+                    vertices.len() - 1
+                });
+                sums[group] += tangent * angle;
+                indices.push(group as u32);
+            }
+        }
+
+        for (vertex, sum) in vertices.iter_mut().zip(&sums) {
+            let normal = normalize_or_zero(&vertex.normal);
+            let mut tangent = normalize_or_zero(&project(sum, &normal));
+            if tangent.norm_squared() == 0.0 {
+                // no uv gradient, pick any direction in the tangent plane
+                let axis = if normal.x.abs() < 0.9 {
+                    Vec3::x()
+                } else {
+                    Vec3::y()
+                };
+                tangent = normalize_or_zero(&project(&axis, &normal));
+            }
+            vertex.tangent = Vec4::new(tangent.x, tangent.y, tangent.z, vertex.tangent.w);
+        }
+
+        TangentMesh { vertices, indices }
+    }
+}
@@ -0,0 +1,356 @@
+/*
+* SPDX-License-Identifier: MIT
+*/
+
+use std::collections::HashMap;
+use std::f32::consts::{FRAC_PI_2, PI, TAU};
+
+use nalgebra_glm as glm;
+
+use super::primitives::{Mesh, Vertex};
+use super::surfaces::grid_indices;
+use super::{Vec2, Vec3};
+
+/// Point of a profile revolved around the y axis, `normal` lying in the `(r, y)` plane.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct ProfilePoint {
+    pub r: f32,
+    pub y: f32,
+    pub normal: Vec2,
+}
+
+impl ProfilePoint {
+    pub(crate) fn new(r: f32, y: f32, normal: Vec2) -> Self {
+        Self { r, y, normal }
+    }
+}
+
+/// Revolves `profile` by `angle` around the y axis in `segments` steps. A profile going up
+/// with the axis on its left faces outwards; u follows the rotation and v the profile length.
+pub(crate) fn revolve_profile(profile: &[ProfilePoint], angle: f32, segments: usize) -> Mesh {
+    let mut lengths = vec![0.0];
+    for w in profile.windows(2) {
+        let step = Vec2::new(w[1].r - w[0].r, w[1].y - w[0].y).norm();
+        lengths.push(lengths.last().unwrap() + step);
+    }
+    let total = lengths.last().copied().unwrap_or(0.0).max(f32::EPSILON);
+
+    let mut vertices = Vec::with_capacity(profile.len() * (segments + 1));
+    for (point, length) in profile.iter().zip(&lengths) {
+        // snaps the rounding errors of points meant to be on the axis
+        let r = if point.r.abs() <= 1e-6 * total {
+            0.0
+        } else {
+            point.r
+        };
+        for i in 0..=segments {
+            let u = i as f32 / segments as f32;
+            let (sin, cos) = (u * angle).sin_cos();
+            let normal = Vec3::new(point.normal.x * cos, point.normal.y, -point.normal.x * sin);
+            vertices.push(Vertex {
+                position: Vec3::new(r * cos, point.y, -r * sin),
+                normal: normalize_or(&normal, &Vec3::y()),
+                uv: Vec2::new(u, length / total),
+            });
+        }
+    }
+    without_degenerate(Mesh {
+        vertices,
+        indices: grid_indices(segments + 1, profile.len()),
+    })
+}
+
+fn normalize_or(v: &Vec3, fallback: &Vec3) -> Vec3 {
+    if v.norm_squared() > 0.0 {
+        glm::normalize(v)
+    } else {
+        *fallback
+    }
+}
+
+/// Drops the triangles collapsed at poles and apexes.
+pub(crate) fn without_degenerate(mut mesh: Mesh) -> Mesh {
+    let vertices = &mesh.vertices;
+    let indices = mesh
+        .indices
+        .chunks_exact(3)
+        .filter(|t| {
+            let [a, b, c] = [0, 1, 2].map(|k| vertices[t[k] as usize].position);
+            glm::cross(&(b - a), &(c - a)).norm_squared() > 0.0
+        })
+        .flatten()
+        .copied()
+        .collect();
+    mesh.indices = indices;
+    mesh
+}
+
+/// Grid of `segments x segments` cells spanning `origin + s * du + t * dv` facing `cross(du, dv)`.
+fn quad(origin: Vec3, du: Vec3, dv: Vec3, segments: usize) -> Mesh {
+    let normal = glm::normalize(&glm::cross(&du, &dv));
+    let mut vertices = Vec::with_capacity((segments + 1) * (segments + 1));
+    for j in 0..=segments {
+        let v = j as f32 / segments as f32;
+        for i in 0..=segments {
+            let u = i as f32 / segments as f32;
+            vertices.push(Vertex {
+                position: origin + du * u + dv * v,
+                normal,
+                uv: Vec2::new(u, v),
+            });
+        }
+    }
+    Mesh {
+        vertices,
+        indices: grid_indices(segments + 1, segments + 1),
+    }
+}
+
+/// Flat disc in the xz plane at height `y`, facing up or down.
+fn disc(radius: f32, y: f32, segments: usize, up: bool) -> Mesh {
+    let normal = if up { Vec3::y() } else { -Vec3::y() };
+    let mut vertices = vec![Vertex {
+        position: Vec3::new(0.0, y, 0.0),
+        normal,
+        uv: Vec2::new(0.5, 0.5),
+    }];
+    for i in 0..=segments {
+        let (sin, cos) = (i as f32 / segments as f32 * TAU).sin_cos();
+        vertices.push(Vertex {
+            position: Vec3::new(radius * cos, y, -radius * sin),
+            normal,
+            uv: Vec2::new(0.5 + 0.5 * cos, 0.5 - 0.5 * sin),
+        });
+    }
+    let mut indices = Vec::with_capacity(segments * 3);
+    for i in 1..=segments as u32 {
+        // the ring turns counter-clockwise seen from above
+        if up {
+            indices.extend([0, i, i + 1]);
+        } else {
+            indices.extend([0, i + 1, i]);
+        }
+    }
+    Mesh { vertices, indices }
+}
+
+/// Plane of `size` in the xz plane facing up, centered on the origin.
+pub fn plane(size: Vec2, columns: usize, rows: usize) -> Mesh {
+    let mut vertices = Vec::with_capacity((columns + 1) * (rows + 1));
+    for j in 0..=rows {
+        let v = j as f32 / rows as f32;
+        for i in 0..=columns {
+            let u = i as f32 / columns as f32;
+            vertices.push(Vertex {
+                position: Vec3::new((u - 0.5) * size.x, 0.0, (0.5 - v) * size.y),
+                normal: Vec3::y(),
+                uv: Vec2::new(u, v),
+            });
+        }
+    }
+    Mesh {
+        vertices,
+        indices: grid_indices(columns + 1, rows + 1),
+    }
+}
+
+/// Box of `size` centered on the origin, every face split in `segments x segments`
+/// cells and mapped to the whole texture.
+pub fn cuboid(size: Vec3, segments: usize) -> Mesh {
+    let mut mesh = Mesh {
+        vertices: Vec::new(),
+        indices: Vec::new(),
+    };
+    let faces = [
+        (Vec3::x(), Vec3::y()),
+        (-Vec3::x(), Vec3::y()),
+        (Vec3::y(), -Vec3::z()),
+        (-Vec3::y(), Vec3::z()),
+        (Vec3::z(), Vec3::y()),
+        (-Vec3::z(), Vec3::y()),
+    ];
+    for (normal, up) in faces {
+        let right = glm::cross(&up, &normal);
+        let (du, dv) = (right.component_mul(&size), up.component_mul(&size));
+        let origin = normal.component_mul(&size) * 0.5 - du * 0.5 - dv * 0.5;
+        mesh.append(quad(origin, du, dv, segments));
+    }
+    mesh
+}
+
+/// Sphere of `radius` centered on the origin made of `segments` meridians and `rings` parallels.
+pub fn uv_sphere(radius: f32, segments: usize, rings: usize) -> Mesh {
+    let profile: Vec<ProfilePoint> = (0..=rings)
+        .map(|j| {
+            let (sin, cos) = (j as f32 / rings as f32 * PI - FRAC_PI_2).sin_cos();
+            ProfilePoint::new(radius * cos, radius * sin, Vec2::new(cos, sin))
+        })
+        .collect();
+    revolve_profile(&profile, TAU, segments)
+}
+
+/// Sphere of `radius` from an icosahedron whose triangles are split `subdivisions` times.
+/// Texture coordinates follow longitude and latitude, vertices on the seam are duplicated.
+pub fn icosphere(radius: f32, subdivisions: usize) -> Mesh {
+    let phi = (1.0 + 5.0_f32.sqrt()) / 2.0;
+    let mut positions: Vec<Vec3> = [
+        (-1.0, phi, 0.0),
+        (1.0, phi, 0.0),
+        (-1.0, -phi, 0.0),
+        (1.0, -phi, 0.0),
+        (0.0, -1.0, phi),
+        (0.0, 1.0, phi),
+        (0.0, -1.0, -phi),
+        (0.0, 1.0, -phi),
+        (phi, 0.0, -1.0),
+        (phi, 0.0, 1.0),
+        (-phi, 0.0, -1.0),
+        (-phi, 0.0, 1.0),
+    ]
+    .iter()
+    .map(|&(x, y, z)| {
+        // turned around z so that the first and fourth vertices are the poles
+        let (sin, cos) = (-(1.0 / phi).atan()).sin_cos();
+        glm::normalize(&Vec3::new(x * cos - y * sin, x * sin + y * cos, z))
+    })
+    .collect();
+    let mut triangles: Vec<[u32; 3]> = vec![
+        [0, 11, 5],
+        [0, 5, 1],
+        [0, 1, 7],
+        [0, 7, 10],
+        [0, 10, 11],
+        [1, 5, 9],
+        [5, 11, 4],
+        [11, 10, 2],
+        [10, 7, 6],
+        [7, 1, 8],
+        [3, 9, 4],
+        [3, 4, 2],
+        [3, 2, 6],
+        [3, 6, 8],
+        [3, 8, 9],
+        [4, 9, 5],
+        [2, 4, 11],
+        [6, 2, 10],
+        [8, 6, 7],
+        [9, 8, 1],
+    ];
+    for _ in 0..subdivisions {
+        let mut midpoints: HashMap<(u32, u32), u32> = HashMap::new();
+        let mut midpoint = |a: u32, b: u32| {
+            *midpoints.entry((a.min(b), a.max(b))).or_insert_with(|| {
+                let p = glm::normalize(&(positions[a as usize] + positions[b as usize]));
+                positions.push(p);
+                positions.len() as u32 - 1
+            })
+        };
+        triangles = triangles
+            .iter()
+            .flat_map(|&[a, b, c]| {
+                let (ab, bc, ca) = (midpoint(a, b), midpoint(b, c), midpoint(c, a));
+                [[a, ab, ca], [b, bc, ab], [c, ca, bc], [ab, bc, ca]]
+            })
+            .collect();
+    }
+
+    let longitude = |p: &Vec3| 0.5 + p.z.atan2(-p.x) / TAU;
+    let mut mesh = Mesh {
+        vertices: Vec::with_capacity(triangles.len() * 3),
+        indices: (0..triangles.len() as u32 * 3).collect(),
+    };
+    for triangle in &triangles {
+        let corners = triangle.map(|v| positions[v as usize]);
+        let poles = corners.map(|p| p.x.abs() < 1e-6 && p.z.abs() < 1e-6);
+        let mut u = corners.map(|p| longitude(&p));
+        // triangles across the seam wrap around to u > 1
+        let max = (0..3)
+            .filter(|&k| !poles[k])
+            .map(|k| u[k])
+            .fold(0.0, f32::max);
+        for value in u.iter_mut() {
+            if max - *value > 0.5 {
+                *value += 1.0;
+            }
+        }
+        for (k, p) in corners.iter().enumerate() {
+            // the longitude of a pole is taken from the rest of the triangle
+            let u = if poles[k] {
+                (u[(k + 1) % 3] + u[(k + 2) % 3]) * 0.5
+            } else {
+                u[k]
+            };
+            mesh.vertices.push(Vertex {
+                position: p * radius,
+                normal: *p,
+                uv: Vec2::new(u, 0.5 + p.y.clamp(-1.0, 1.0).asin() / PI),
+            });
+        }
+    }
+    mesh.weld_matching_uv(1e-6 * radius.abs().max(1.0));
+    mesh
+}
+
+/// Capped cylinder of `radius` and `height` standing on the y axis, centered on the origin.
+pub fn cylinder(radius: f32, height: f32, segments: usize, stacks: usize) -> Mesh {
+    let profile: Vec<ProfilePoint> = (0..=stacks)
+        .map(|j| {
+            let y = (j as f32 / stacks as f32 - 0.5) * height;
+            ProfilePoint::new(radius, y, Vec2::new(1.0, 0.0))
+        })
+        .collect();
+    let mut mesh = revolve_profile(&profile, TAU, segments);
+    mesh.append(disc(radius, height * 0.5, segments, true));
+    mesh.append(disc(radius, -height * 0.5, segments, false));
+    mesh
+}
+
+/// Cone with a base of `radius` and its apex `height` above, centered on the origin.
+pub fn cone(radius: f32, height: f32, segments: usize, stacks: usize) -> Mesh {
+    let normal = glm::normalize(&Vec2::new(height, radius));
+    let profile: Vec<ProfilePoint> = (0..=stacks)
+        .map(|j| {
+            let t = j as f32 / stacks as f32;
+            ProfilePoint::new(radius * (1.0 - t), (t - 0.5) * height, normal)
+        })
+        .collect();
+    let mut mesh = revolve_profile(&profile, TAU, segments);
+    mesh.append(disc(radius, -height * 0.5, segments, false));
+    mesh
+}
+
+/// Cylinder of `height` between two hemispheres of `radius`, each made of `rings` parallels.
+pub fn capsule(radius: f32, height: f32, segments: usize, rings: usize) -> Mesh {
+    let mut profile = Vec::with_capacity(2 * rings + 2);
+    for (offset, start) in [(-height * 0.5, -FRAC_PI_2), (height * 0.5, 0.0)] {
+        for j in 0..=rings {
+            let (sin, cos) = (start + j as f32 / rings as f32 * FRAC_PI_2).sin_cos();
+            profile.push(ProfilePoint::new(
+                radius * cos,
+                offset + radius * sin,
+                Vec2::new(cos, sin),
+            ));
+        }
+    }
+    revolve_profile(&profile, TAU, segments)
+}
+
+/// Torus around the y axis, its tube of `minor_radius` circling at `major_radius`.
+pub fn torus(
+    major_radius: f32,
+    minor_radius: f32,
+    major_segments: usize,
+    minor_segments: usize,
+) -> Mesh {
+    let profile: Vec<ProfilePoint> = (0..=minor_segments)
+        .map(|j| {
+            let (sin, cos) = (j as f32 / minor_segments as f32 * TAU - PI).sin_cos();
+            ProfilePoint::new(
+                major_radius + minor_radius * cos,
+                minor_radius * sin,
+                Vec2::new(cos, sin),
+            )
+        })
+        .collect();
+    revolve_profile(&profile, TAU, major_segments)
+}
@@ -0,0 +1,574 @@
+/*
+* SPDX-License-Identifier: MIT
+*/
+
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap};
+
+use nalgebra_glm as glm;
+
+use super::primitives::Mesh;
+use super::processing::merge_vertices;
+use super::Vec3;
+
+/// When to stop simplifying a mesh.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SimplifyTarget {
+    /// Collapse edges until the mesh has at most this many triangles.
+    Triangles(usize),
+    /// Collapse edges as long as the error stays below this distance.
+    Error(f32),
+}
+
+/// A simplified mesh with its error, the root mean square distance of its
+/// vertices to the planes of the original triangles they replace.
+#[derive(Clone, Debug)]
+pub struct Lod {
+    pub mesh: Mesh,
+    pub error: f32,
+}
+
+/// Relative distance under which vertices are considered to share a position.
+const SEAM_TOLERANCE: f32 = 1e-5;
+/// Weight of the planes keeping borders in place, relative to the triangle planes.
+const BORDER_WEIGHT: f64 = 10.0;
+/// Smallest cosine between the original normal of a triangle and its normal after a collapse.
+const MIN_NORMAL_COSINE: f32 = 0.5;
+/// Largest error of a collapse when simplifying to a triangle count, relative to the
+/// diagonal of the bounding box of the mesh.
+const MAX_RELATIVE_ERROR: f32 = 0.05;
+
+/// Symmetric 4x4 matrix summing the squared distances to a set of planes,
+/// along with the total weight of those planes.
+#[derive(Clone, Copy, Debug, Default)]
+struct Quadric {
+    m: [f64; 10],
+    weight: f64,
+}
+
+impl Quadric {
+    /// Plane through `point` with the unit `normal`.
+    fn plane(point: &Vec3, normal: &Vec3, weight: f64) -> Self {
+        let [a, b, c] = [normal.x as f64, normal.y as f64, normal.z as f64];
+        let d = -(a * point.x as f64 + b * point.y as f64 + c * point.z as f64);
+        let m = [
+            a * a,
+            a * b,
+            a * c,
+            a * d,
+            b * b,
+            b * c,
+            b * d,
+            c * c,
+            c * d,
+            d * d,
+        ];
+        Self {
+            m: m.map(|x| x * weight),
+            weight,
+        }
+    }
+
+    fn add(&mut self, other: &Quadric) {
+        for (a, b) in self.m.iter_mut().zip(&other.m) {
+            *a += b;
+        }
+        self.weight += other.weight;
+    }
+
+    /// Weighted sum of the squared distances of `p` to the planes.
+    fn evaluate(&self, p: &Vec3) -> f64 {
+        let [x, y, z] = [p.x as f64, p.y as f64, p.z as f64];
+        let m = &self.m;
+        let value = m[0] * x * x
+            + 2.0 * m[1] * x * y
+            + 2.0 * m[2] * x * z
+            + 2.0 * m[3] * x
+            + m[4] * y * y
+            + 2.0 * m[5] * y * z
+            + 2.0 * m[6] * y
+            + m[7] * z * z
+            + 2.0 * m[8] * z
+            + m[9];
+        value.max(0.0)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    /// Inside the mesh, on a uv seam it only slides along the seam.
+    Interior,
+    /// On a border of the mesh, can only slide along it.
+    Border,
+    /// On a non manifold edge or border, never moves.
+    Locked,
+}
+
+/// Candidate collapse of the position `from` onto the position `to`.
+struct Collapse {
+    cost: f64,
+    from: u32,
+    to: u32,
+    versions: (u32, u32),
+}
+
+impl PartialEq for Collapse {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Collapse {}
+
+impl PartialOrd for Collapse {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Collapse {
+    // reversed so that the binary heap pops the cheapest collapse first
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.cost.total_cmp(&self.cost)
+    }
+}
+
+/// Collapses are made between positions, the vertices sharing a position on a uv
+/// seam moving together onto the vertices of the target on the same side of the seam.
+struct Simplifier<'a> {
+    positions: Vec<Vec3>,
+    triangles: Vec<[u32; 3]>,
+    /// Unit normals of the original triangles, zero for degenerate ones.
+    normals: Vec<Vec3>,
+    removed: Vec<bool>,
+    /// Triangles around every vertex, may refer to removed triangles.
+    adjacency: Vec<Vec<usize>>,
+    /// Position of every vertex.
+    groups: Vec<u32>,
+    /// Vertices at every position.
+    members: Vec<Vec<u32>>,
+    quadrics: Vec<Quadric>,
+    kinds: Vec<Kind>,
+    versions: Vec<u32>,
+    heap: BinaryHeap<Collapse>,
+    /// Largest error of a collapse when simplifying to a triangle count.
+    max_error: f32,
+    mesh: &'a Mesh,
+}
+
+impl<'a> Simplifier<'a> {
+    fn new(mesh: &'a Mesh) -> Self {
+        let positions: Vec<Vec3> = mesh.vertices.iter().map(|v| v.position).collect();
+        let triangles: Vec<[u32; 3]> = mesh
+            .indices
+            .chunks_exact(3)
+            .map(|t| [t[0], t[1], t[2]])
+            .collect();
+        let mut adjacency = vec![Vec::new(); positions.len()];
+        for (index, triangle) in triangles.iter().enumerate() {
+            for &vertex in triangle {
+                adjacency[vertex as usize].push(index);
+            }
+        }
+
+        // vertices sharing a position lie on a uv seam
+        let (min, max) = positions.iter().fold(
+            (Vec3::repeat(f32::MAX), Vec3::repeat(f32::MIN)),
+            |(min, max), p| (glm::min2(&min, p), glm::max2(&max, p)),
+        );
+        let size = if positions.is_empty() {
+            0.0
+        } else {
+            glm::distance(&min, &max)
+        };
+        let scale = positions
+            .iter()
+            .fold(0.0_f32, |scale, p| scale.max(p.amax()))
+            .max(1.0);
+        let (merged, groups) = merge_vertices(&mesh.vertices, SEAM_TOLERANCE * scale, |_, _| true);
+        let mut members = vec![Vec::new(); merged.len()];
+        for (vertex, &group) in groups.iter().enumerate() {
+            members[group as usize].push(vertex as u32);
+        }
+
+        let mut quadrics = vec![Quadric::default(); merged.len()];
+        let mut normals = Vec::with_capacity(triangles.len());
+        let mut locked = vec![false; merged.len()];
+        let mut vertex_edges: HashMap<(u32, u32), usize> = HashMap::new();
+        let mut group_edges: HashMap<(u32, u32), usize> = HashMap::new();
+        for triangle in &triangles {
+            let [a, b, c] = triangle.map(|v| positions[v as usize]);
+            let cross = glm::cross(&(b - a), &(c - a));
+            let area = cross.norm();
+            let triangle_groups = triangle.map(|v| groups[v as usize]);
+            if area == 0.0 {
+                normals.push(Vec3::zeros());
+            } else {
+                normals.push(cross / area);
+                let quadric = Quadric::plane(&a, &(cross / area), area as f64 * 0.5);
+                for &group in &triangle_groups {
+                    quadrics[group as usize].add(&quadric);
+                }
+            }
+            for k in 0..3 {
+                let edge = |ends: [u32; 3]| {
+                    let (a, b) = (ends[k], ends[(k + 1) % 3]);
+                    (a.min(b), a.max(b))
+                };
+                *vertex_edges.entry(edge(*triangle)).or_default() += 1;
+                let (a, b) = edge(triangle_groups);
+                if a == b {
+                    // the triangle collapsed to a point or an edge
+                    locked[a as usize] = true;
+                } else {
+                    *group_edges.entry((a, b)).or_default() += 1;
+                }
+            }
+        }
+
+        // planes through the border and seam edges, perpendicular to their triangle
+        for (index, triangle) in triangles.iter().enumerate() {
+            for k in 0..3 {
+                let (a, b) = (triangle[k], triangle[(k + 1) % 3]);
+                if vertex_edges[&(a.min(b), a.max(b))] != 1 {
+                    continue;
+                }
+                let pa = positions[a as usize];
+                let edge = positions[b as usize] - pa;
+                let perpendicular = glm::cross(&edge, &normals[index]);
+                if perpendicular.norm_squared() == 0.0 {
+                    continue;
+                }
+                let weight = edge.norm_squared() as f64 * BORDER_WEIGHT;
+                let quadric = Quadric::plane(&pa, &glm::normalize(&perpendicular), weight);
+                quadrics[groups[a as usize] as usize].add(&quadric);
+                quadrics[groups[b as usize] as usize].add(&quadric);
+            }
+        }
+
+        let mut border_edges = vec![0; merged.len()];
+        for (&(a, b), &count) in &group_edges {
+            if count == 1 {
+                border_edges[a as usize] += 1;
+                border_edges[b as usize] += 1;
+            } else if count > 2 {
+                locked[a as usize] = true;
+                locked[b as usize] = true;
+            }
+        }
+        let kinds = border_edges
+            .iter()
+            .zip(&locked)
+            .map(|(&count, &locked)| match count {
+                _ if locked => Kind::Locked,
+                0 => Kind::Interior,
+                // a vertex on a manifold border has two border edges
+                2 => Kind::Border,
+                _ => Kind::Locked,
+            })
+            .collect();
+
+        let mut simplifier = Self {
+            removed: vec![false; triangles.len()],
+            versions: vec![0; merged.len()],
+            positions,
+            triangles,
+            normals,
+            adjacency,
+            groups,
+            members,
+            quadrics,
+            kinds,
+            heap: BinaryHeap::new(),
+            max_error: size * MAX_RELATIVE_ERROR,
+            mesh,
+        };
+        for group in 0..merged.len() as u32 {
+            simplifier.push_collapses(group);
+        }
+        simplifier
+    }
+
+    fn position(&self, group: u32) -> Vec3 {
+        self.positions[self.members[group as usize][0] as usize]
+    }
+
+    fn live_triangles(&self, vertex: u32) -> impl Iterator<Item = usize> + '_ {
+        self.adjacency[vertex as usize]
+            .iter()
+            .copied()
+            .filter(|&t| !self.removed[t])
+    }
+
+    fn group_triangles(&self, group: u32) -> impl Iterator<Item = usize> + '_ {
+        self.members[group as usize]
+            .iter()
+            .flat_map(|&vertex| self.live_triangles(vertex))
+    }
+
+    fn triangle_groups(&self, triangle: usize) -> [u32; 3] {
+        self.triangles[triangle].map(|v| self.groups[v as usize])
+    }
+
+    fn neighbours(&self, group: u32) -> Vec<u32> {
+        let mut neighbours: Vec<u32> = self
+            .group_triangles(group)
+            .flat_map(|t| self.triangle_groups(t))
+            .filter(|&g| g != group)
+            .collect();
+        neighbours.sort_unstable();
+        neighbours.dedup();
+        neighbours
+    }
+
+    fn edge_triangles(&self, a: u32, b: u32) -> usize {
+        self.group_triangles(a)
+            .filter(|&t| self.triangle_groups(t).contains(&b))
+            .count()
+    }
+
+    fn push_collapses(&mut self, from: u32) {
+        for to in self.neighbours(from) {
+            self.push_collapse(from, to);
+        }
+    }
+
+    fn push_collapse(&mut self, from: u32, to: u32) {
+        let kind = self.kinds[from as usize];
+        if kind == Kind::Locked || (kind == Kind::Border && self.edge_triangles(from, to) != 1) {
+            return;
+        }
+        let mut quadric = self.quadrics[from as usize];
+        quadric.add(&self.quadrics[to as usize]);
+        self.heap.push(Collapse {
+            cost: quadric.evaluate(&self.position(to)),
+            from,
+            to,
+            versions: (self.versions[from as usize], self.versions[to as usize]),
+        });
+    }
+
+    /// Vertices at the position `from` paired with the vertex at `to` they collapse onto,
+    /// if the collapse keeps the mesh manifold, keeps the uv seams and does not fold triangles.
+    fn valid_collapse(&self, from: u32, to: u32) -> Option<Vec<(u32, u32)>> {
+        let shared = self.edge_triangles(from, to);
+        if shared == 0 {
+            return None;
+        }
+        let to_neighbours = self.neighbours(to);
+        let common = self
+            .neighbours(from)
+            .iter()
+            .filter(|g| to_neighbours.binary_search(g).is_ok())
+            .count();
+        if common != shared {
+            return None;
+        }
+
+        // every vertex must see a single vertex of the target, so that the vertices
+        // on a seam only move along it
+        let mut pairs = Vec::new();
+        for &vertex in &self.members[from as usize] {
+            let mut targets = self
+                .live_triangles(vertex)
+                .flat_map(|t| self.triangles[t])
+                .filter(|&v| self.groups[v as usize] == to);
+            match targets.next() {
+                Some(target) if targets.all(|v| v == target) => pairs.push((vertex, target)),
+                Some(_) => return None,
+                None if self.live_triangles(vertex).next().is_some() => return None,
+                None => {}
+            }
+        }
+
+        // triangles are compared to their original orientation so that small turns
+        // cannot add up to a fold
+        let target = self.position(to);
+        let keeps_orientation = self
+            .group_triangles(from)
+            .filter(|&t| !self.triangle_groups(t).contains(&to))
+            .all(|t| {
+                let [a, b, c] = self.triangles[t].map(|v| {
+                    if self.groups[v as usize] == from {
+                        target
+                    } else {
+                        self.positions[v as usize]
+                    }
+                });
+                let after = glm::cross(&(b - a), &(c - a));
+                let length = after.norm();
+                let normal = self.normals[t];
+                length > 0.0
+                    && glm::dot(&normal, &after) >= MIN_NORMAL_COSINE * length * normal.norm()
+            });
+        keeps_orientation.then_some(pairs)
+    }
+
+    fn collapse(&mut self, from: u32, to: u32, pairs: &[(u32, u32)]) -> usize {
+        let mut removed = 0;
+        for &(vertex, target) in pairs {
+            let triangles: Vec<usize> = self.live_triangles(vertex).collect();
+            for t in triangles {
+                if self.triangles[t].contains(&target) {
+                    self.removed[t] = true;
+                    removed += 1;
+                } else {
+                    for v in self.triangles[t].iter_mut() {
+                        if *v == vertex {
+                            *v = target;
+                        }
+                    }
+                    self.adjacency[target as usize].push(t);
+                }
+            }
+        }
+        let quadric = self.quadrics[from as usize];
+        self.quadrics[to as usize].add(&quadric);
+        self.kinds[from as usize] = Kind::Locked;
+        self.members[from as usize].clear();
+
+        // only the collapses involving `to` changed cost
+        self.versions[to as usize] += 1;
+        self.push_collapses(to);
+        for group in self.neighbours(to) {
+            self.push_collapse(group, to);
+        }
+        removed
+    }
+
+    fn run(&mut self, target: SimplifyTarget) -> f32 {
+        let mut count = self.triangles.len();
+        let mut error: f32 = 0.0;
+        let limit = match target {
+            SimplifyTarget::Triangles(_) => self.max_error,
+            SimplifyTarget::Error(limit) => limit,
+        };
+        while let Some(candidate) = self.heap.pop() {
+            if let SimplifyTarget::Triangles(triangles) = target {
+                if count <= triangles {
+                    break;
+                }
+            }
+            let (from, to) = (candidate.from, candidate.to);
+            if candidate.versions != (self.versions[from as usize], self.versions[to as usize])
+                || self.kinds[from as usize] == Kind::Locked
+            {
+                continue;
+            }
+            let mut quadric = self.quadrics[from as usize];
+            quadric.add(&self.quadrics[to as usize]);
+            let cost = if quadric.weight > 0.0 {
+                (candidate.cost / quadric.weight).sqrt() as f32
+            } else {
+                0.0
+            };
+            if cost > limit {
+                continue;
+            }
+            let Some(pairs) = self.valid_collapse(from, to) else {
+                continue;
+            };
+            count -= self.collapse(from, to, &pairs);
+            error = error.max(cost);
+        }
+        error
+    }
+
+    fn into_mesh(self) -> Mesh {
+        let mut remap = vec![u32::MAX; self.positions.len()];
+        let mut vertices = Vec::new();
+        let mut indices = Vec::new();
+        for (triangle, removed) in self.triangles.iter().zip(&self.removed) {
+            if *removed {
+                continue;
+            }
+            for &vertex in triangle {
+                if remap[vertex as usize] == u32::MAX {
+                    remap[vertex as usize] = vertices.len() as u32;
+                    vertices.push(self.mesh.vertices[vertex as usize]);
+                }
+                indices.push(remap[vertex as usize]);
+            }
+        }
+        Mesh { vertices, indices }
+    }
+}
+
+impl Mesh {
+    /// Simplifies the mesh by quadric error edge collapses, moving vertices onto their
+    /// neighbours so that their attributes are kept.
+    /// Borders only shrink along themselves, vertices on uv seams only slide along the seam
+    /// together with their twins, and collapses stop once they would distort the mesh, so
+    /// the target may not be reached.
+    pub fn simplify(&self, target: SimplifyTarget) -> Lod {
+        let mut simplifier = Simplifier::new(self);
+        let error = simplifier.run(target);
+        Lod {
+            mesh: simplifier.into_mesh(),
+            error,
+        }
+    }
+
+    /// Builds up to `levels` meshes, starting with this one, each having about `ratio`
+    /// times the triangles of the previous one. Stops early once simplification stalls.
+    pub fn lod_chain(&self, levels: usize, ratio: f32) -> Vec<Lod> {
+        let mut chain = vec![Lod {
+            mesh: self.clone(),
+            error: 0.0,
+        }];
+        let mut target = self.indices.len() as f32 / 3.0;
+        while chain.len() < levels {
+            let triangles = chain.last().unwrap().mesh.indices.len() / 3;
+            target *= ratio;
+            // simplifying the original mesh keeps the errors relative to it
+            let lod = self.simplify(SimplifyTarget::Triangles(target as usize));
+            if lod.mesh.indices.len() / 3 >= triangles {
+                break;
+            }
+            chain.push(lod);
+        }
+        chain
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::PI;
+
+    use nalgebra_glm as glm;
+
+    use super::SimplifyTarget;
+    use crate::geometry::primitives::Mesh;
+    use crate::geometry::shapes::icosphere;
+
+    /// Volume enclosed by the mesh, asserting that every triangle faces away from the origin.
+    fn outward_volume(mesh: &Mesh) -> f32 {
+        mesh.indices
+            .chunks_exact(3)
+            .map(|t| {
+                let [a, b, c] = [0, 1, 2].map(|k| mesh.vertices[t[k] as usize].position);
+                let normal = glm::cross(&(b - a), &(c - a));
+                assert!(
+                    glm::dot(&normal, &(a + b + c)) > 0.0,
+                    "triangle faces inward"
+                );
+                glm::dot(&a, &glm::cross(&b, &c)) / 6.0
+            })
+            .sum()
+    }
+
+    #[test]
+    fn icosphere_lods_keep_their_shape() {
+        let sphere = icosphere(1.0, 4);
+        let volume = 4.0 / 3.0 * PI;
+        let chain = sphere.lod_chain(6, 0.5);
+        assert_eq!(chain.len(), 6);
+        for lod in &chain {
+            assert!(outward_volume(&lod.mesh) > 0.85 * volume);
+        }
+
+        let lod = sphere.simplify(SimplifyTarget::Triangles(100));
+        assert!(lod.mesh.indices.len() / 3 <= 100);
+        assert!(outward_volume(&lod.mesh) > 0.8 * volume);
+    }
+}
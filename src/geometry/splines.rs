@@ -2,7 +2,444 @@
 * SPDX-License-Identifier: MIT
 */
 
-use super::{algorithms::de_casteljau, primitives::PolyLine, Vec3};
+use std::f32::consts::{FRAC_PI_2, TAU};
+use std::fmt;
+
+use nalgebra_glm as glm;
+
+use super::{
+    algorithms::{
+        basis_functions_derivatives, binomial, de_boor, de_casteljau, elevate_degree, find_span,
+        gauss_legendre, hodograph, reduce_degree, subdivide,
+    },
+    primitives::PolyLine,
+    Vec3, Vec4,
+};
+
+#[derive(Debug)]
+pub enum SplineError {
+    NotEnoughControlPoints,
+    InvalidKnotCount { expected: usize, found: usize },
+    DecreasingKnots,
+    EmptyDomain,
+    ParameterOutOfDomain,
+    KnotMultiplicity,
+    DegreeMismatch,
+    InvalidWeights,
+    IrregularControlGrid,
+    TangentCountMismatch,
+    NotEnoughSections,
+    SingularInterpolation,
+}
+
+impl fmt::Display for SplineError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotEnoughControlPoints => {
+                write!(f, "A spline needs more control points than its degree")
+            }
+            Self::InvalidKnotCount { expected, found } => {
+                write!(f, "Expected {} knots, found {}", expected, found)
+            }
+            Self::DecreasingKnots => write!(f, "Knot vector must be non-decreasing"),
+            Self::EmptyDomain => write!(f, "Knot vector defines an empty parameter domain"),
+            Self::ParameterOutOfDomain => write!(f, "Parameter is outside of the spline domain"),
+            Self::KnotMultiplicity => write!(f, "Knot multiplicity would exceed the degree"),
+            Self::DegreeMismatch => write!(f, "Spline degree does not match the Bezier order"),
+            Self::InvalidWeights => {
+                write!(f, "Expected one strictly positive weight per control point")
+            }
+            Self::IrregularControlGrid => {
+                write!(f, "All rows of a control grid must have the same length")
+            }
+            Self::TangentCountMismatch => write!(f, "Expected exactly one tangent per point"),
+            Self::NotEnoughSections => write!(f, "Not enough cross sections to loft"),
+            Self::SingularInterpolation => {
+                write!(f, "Interpolated points do not define a unique spline")
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Frame {
+    pub origin: Vec3,
+    pub tangent: Vec3,
+    pub normal: Vec3,
+    pub binormal: Vec3,
+}
+
+/// Result of projecting a point on a curve.
+#[derive(Clone, Copy, Debug)]
+pub struct CurveProjection {
+    pub parameter: f32,
+    pub point: Vec3,
+    pub distance: f32,
+}
+
+pub trait Curve {
+    fn domain(&self) -> (f32, f32) {
+        (0.0, 1.0)
+    }
+
+    fn point(&self, t: f32) -> Vec3;
+
+    /// Derivative of the given `order` at `t`, order 0 being the position.
+    fn derivative(&self, t: f32, order: usize) -> Vec3;
+
+    /// Length of the control polygon, the scale of the curve.
+    fn control_polygon_length(&self) -> f32;
+
+    /// Norm under which a derivative is considered to vanish.
+    fn vanishing_tolerance(&self) -> f32 {
+        self.control_polygon_length() * VANISHING_DERIVATIVE
+    }
+
+    /// Unit tangent, falling back to the first non-vanishing higher derivative at cusps.
+    fn tangent(&self, t: f32) -> Vec3 {
+        let tolerance = self.vanishing_tolerance();
+        for order in 1..=3 {
+            let d = self.derivative(t, order);
+            if d.norm() > tolerance {
+                return glm::normalize(&d);
+            }
+        }
+        Vec3::zeros()
+    }
+
+    fn curvature(&self, t: f32) -> f32 {
+        let d1 = self.derivative(t, 1);
+        let d2 = self.derivative(t, 2);
+        let speed = d1.norm();
+        if speed <= self.vanishing_tolerance() {
+            return 0.0;
+        }
+        glm::cross(&d1, &d2).norm() / speed.powi(3)
+    }
+
+    fn torsion(&self, t: f32) -> f32 {
+        let d1 = self.derivative(t, 1);
+        let d2 = self.derivative(t, 2);
+        let b = glm::cross(&d1, &d2);
+        let len = b.norm_squared();
+        if len.sqrt() <= self.vanishing_tolerance() * self.control_polygon_length() {
+            return 0.0;
+        }
+        glm::dot(&b, &self.derivative(t, 3)) / len
+    }
+
+    /// Frenet frame at `t`. On straight parts the normal is an arbitrary perpendicular of the tangent.
+    fn frenet_frame(&self, t: f32) -> Frame {
+        let tangent = self.tangent(t);
+        let b = glm::cross(&self.derivative(t, 1), &self.derivative(t, 2));
+        let binormal = if b.norm() > self.vanishing_tolerance() * self.control_polygon_length() {
+            glm::normalize(&b)
+        } else {
+            glm::cross(&tangent, &any_perpendicular(&tangent))
+        };
+        Frame {
+            origin: self.point(t),
+            tangent,
+            normal: glm::cross(&binormal, &tangent),
+            binormal,
+        }
+    }
+
+    /// `count` frames evenly spaced over the domain, propagated from the Frenet frame at the
+    /// start with the double reflection method so that they do not twist around the tangent.
+    fn rotation_minimizing_frames(&self, count: usize) -> Vec<Frame> {
+        let (start, end) = self.domain();
+        let count = count.max(2);
+        let mut frames = Vec::with_capacity(count);
+        frames.push(self.frenet_frame(start));
+        for i in 1..count {
+            let d = i as f32 / (count - 1) as f32;
+            let t = start * (1.0 - d) + end * d;
+            let previous = frames[i - 1];
+            let origin = self.point(t);
+            let tangent = self.tangent(t);
+
+            let reflect = |v: &Vec3, axis: &Vec3| {
+                let c = axis.norm_squared();
+                if c <= f32::EPSILON {
+                    *v
+                } else {
+                    v - axis * (2.0 / c * glm::dot(axis, v))
+                }
+            };
+            let v1 = origin - previous.origin;
+            let normal_l = reflect(&previous.normal, &v1);
+            let tangent_l = reflect(&previous.tangent, &v1);
+            let normal = reflect(&normal_l, &(tangent - tangent_l));
+            frames.push(Frame {
+                origin,
+                tangent,
+                normal,
+                binormal: glm::cross(&tangent, &normal),
+            });
+        }
+        frames
+    }
+
+    /// Parameters where the curve may not be smooth, both ends of the domain included.
+    fn breakpoints(&self) -> Vec<f32> {
+        let (start, end) = self.domain();
+        vec![start, end]
+    }
+
+    /// Length of the curve between the parameters `a` and `b`, by Gaussian quadrature.
+    fn length_between(&self, a: f32, b: f32) -> f32 {
+        if b < a {
+            return -self.length_between(b, a);
+        }
+        let mut bounds = vec![a];
+        bounds.extend(self.breakpoints().into_iter().filter(|&k| k > a && k < b));
+        bounds.push(b);
+        bounds
+            .windows(2)
+            .map(|w| smooth_length(self, w[0], w[1]))
+            .sum()
+    }
+
+    fn arc_length(&self) -> f32 {
+        let (start, end) = self.domain();
+        self.length_between(start, end)
+    }
+
+    /// Inverse of the arc length: parameter at which the curve reaches `length` from its start.
+    fn parameter_at_length(&self, length: f32) -> f32 {
+        ArcLengthTable::new(self).parameter_at(self, length)
+    }
+
+    /// Points evenly spaced by `step` along the curve. The end of the curve is appended
+    /// when the length is not a multiple of `step`, making the last interval shorter.
+    fn sample_by_distance(&self, step: f32) -> PolyLine {
+        let (start, end) = self.domain();
+        let mut points = vec![self.point(start)];
+        let table = ArcLengthTable::new(self);
+        let total = table.total();
+        if step > 0.0 && total > 0.0 {
+            let count = (total / step).floor() as usize;
+            for i in 1..=count {
+                points.push(self.point(table.parameter_at(self, step * i as f32)));
+            }
+            if total - count as f32 * step > step * 1e-3 {
+                points.push(self.point(end));
+            } else if let Some(last) = points.last_mut() {
+                *last = self.point(end);
+            }
+        }
+        PolyLine {
+            points,
+            line_strip: true,
+        }
+    }
+
+    /// Closest point of the curve to `target`: the curve is sampled between its breakpoints
+    /// and the best sample is refined by Newton iterations on `C'(t) . (C(t) - target) = 0`.
+    fn closest_point(&self, target: &Vec3) -> CurveProjection {
+        let mut samples: Vec<f32> = Vec::new();
+        for w in self.breakpoints().windows(2) {
+            let step = (w[1] - w[0]) / CLOSEST_POINT_SAMPLES as f32;
+            samples.extend((0..CLOSEST_POINT_SAMPLES).map(|i| w[0] + step * i as f32));
+        }
+        samples.push(self.domain().1);
+
+        let distance = |t: f32| glm::distance2(&self.point(t), target);
+        let best = (0..samples.len())
+            .min_by(|&a, &b| distance(samples[a]).total_cmp(&distance(samples[b])))
+            .unwrap_or(0);
+        let lo = samples[best.saturating_sub(1)];
+        let hi = samples[(best + 1).min(samples.len() - 1)];
+
+        let mut t = samples[best];
+        for _ in 0..MAX_NEWTON_ITERATIONS {
+            let offset = self.point(t) - target;
+            let d1 = self.derivative(t, 1);
+            let f = glm::dot(&d1, &offset);
+            let df = glm::dot(&self.derivative(t, 2), &offset) + d1.norm_squared();
+            if df.abs() <= f32::EPSILON {
+                break;
+            }
+            let next = (t - f / df).clamp(lo, hi);
+            if (next - t).abs() <= f32::EPSILON * (hi - lo).max(1.0) {
+                t = next;
+                break;
+            }
+            t = next;
+        }
+        if distance(samples[best]) < distance(t) {
+            t = samples[best];
+        }
+
+        let point = self.point(t);
+        CurveProjection {
+            parameter: t,
+            point,
+            distance: glm::distance(&point, target),
+        }
+    }
+}
+
+const CLOSEST_POINT_SAMPLES: usize = 16;
+/// Relative to the length of the control polygon, covers the rounding of the derivatives.
+const VANISHING_DERIVATIVE: f32 = 16.0 * f32::EPSILON;
+const ARC_LENGTH_SUBDIVISIONS: usize = 4;
+const MAX_NEWTON_ITERATIONS: usize = 32;
+
+/// Length of a piece of the curve without breakpoints.
+fn smooth_length<C: Curve + ?Sized>(curve: &C, a: f32, b: f32) -> f32 {
+    let step = (b - a) / ARC_LENGTH_SUBDIVISIONS as f32;
+    (0..ARC_LENGTH_SUBDIVISIONS)
+        .map(|i| a + step * i as f32)
+        .map(|lo| gauss_legendre(lo, lo + step, |t| curve.derivative(t, 1).norm()))
+        .sum()
+}
+
+/// Arc length of the curve at each of its breakpoints, so that the span holding a given
+/// length is found by a binary search instead of integrating from the start.
+struct ArcLengthTable {
+    breakpoints: Vec<f32>,
+    lengths: Vec<f32>,
+}
+
+impl ArcLengthTable {
+    fn new<C: Curve + ?Sized>(curve: &C) -> Self {
+        let breakpoints = curve.breakpoints();
+        let mut lengths = vec![0.0];
+        for w in breakpoints.windows(2) {
+            lengths.push(lengths[lengths.len() - 1] + smooth_length(curve, w[0], w[1]));
+        }
+        Self {
+            breakpoints,
+            lengths,
+        }
+    }
+
+    fn total(&self) -> f32 {
+        self.lengths[self.lengths.len() - 1]
+    }
+
+    /// Parameter at which the curve reaches `length` from its start, by Newton iterations
+    /// on the arc length of the span holding it, kept in a bisection bracket.
+    fn parameter_at<C: Curve + ?Sized>(&self, curve: &C, length: f32) -> f32 {
+        let last = self.breakpoints.len() - 1;
+        if length <= 0.0 {
+            return self.breakpoints[0];
+        }
+        if length >= self.total() {
+            return self.breakpoints[last];
+        }
+        let span = (self.lengths.partition_point(|&l| l <= length) - 1).min(last - 1);
+        let (from, end) = (self.breakpoints[span], self.breakpoints[span + 1]);
+        let distance = length - self.lengths[span];
+        let span_length = self.lengths[span + 1] - self.lengths[span];
+        if span_length <= 0.0 {
+            return from;
+        }
+        let tolerance = self.total().max(1.0) * 1e-6;
+        let (mut lo, mut hi) = (from, end);
+        let mut t = from + (end - from) * distance / span_length;
+        for _ in 0..MAX_NEWTON_ITERATIONS {
+            let error = smooth_length(curve, from, t) - distance;
+            if error.abs() <= tolerance {
+                break;
+            }
+            if error > 0.0 {
+                hi = t;
+            } else {
+                lo = t;
+            }
+            let speed = curve.derivative(t, 1).norm();
+            let next = t - error / speed;
+            t = if speed > f32::EPSILON && next > lo && next < hi {
+                next
+            } else {
+                (lo + hi) / 2.0
+            };
+        }
+        t
+    }
+}
+
+fn any_perpendicular(v: &Vec3) -> Vec3 {
+    let helper = if v.x.abs() < 0.9 {
+        Vec3::new(1.0, 0.0, 0.0)
+    } else {
+        Vec3::new(0.0, 1.0, 0.0)
+    };
+    glm::normalize(&glm::cross(v, &helper))
+}
+
+/// `steps + 1` evenly spaced parameters, both ends of `[start, end]` included exactly.
+pub(crate) fn parameters(start: f32, end: f32, steps: usize) -> impl Iterator<Item = f32> {
+    let steps = steps.max(1);
+    (0..=steps).map(move |i| {
+        if i == steps {
+            end
+        } else {
+            start + (end - start) * i as f32 / steps as f32
+        }
+    })
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum Flatness {
+    /// Maximum distance between the curve and the polyline.
+    Chordal(f32),
+    /// Maximum angle in radians the polyline turns by between two consecutive points.
+    Angle(f32),
+}
+
+const MAX_FLATTEN_DEPTH: usize = 16;
+
+/// Pushes the end points of the flat enough pieces of the Bezier curve, excluding its start.
+fn flatten(points: &[Vec3], flatness: Flatness, depth: usize, out: &mut Vec<Vec3>) {
+    if depth == 0 || is_flat(points, flatness) {
+        out.push(points[points.len() - 1]);
+        return;
+    }
+    let (left, right) = subdivide(0.5, points);
+    flatten(&left, flatness, depth - 1, out);
+    flatten(&right, flatness, depth - 1, out);
+}
+
+/// The curve lies in the convex hull of its control points, so the control polygon
+/// bounds both its deviation from the chord and how much it turns.
+fn is_flat(points: &[Vec3], flatness: Flatness) -> bool {
+    if points.len() <= 2 {
+        return true;
+    }
+    let (first, last) = (points[0], points[points.len() - 1]);
+    match flatness {
+        Flatness::Chordal(tolerance) => points[1..points.len() - 1]
+            .iter()
+            .all(|p| distance_to_segment(p, &first, &last) <= tolerance),
+        Flatness::Angle(tolerance) => {
+            let legs: Vec<Vec3> = points
+                .windows(2)
+                .map(|w| w[1] - w[0])
+                .filter(|leg| leg.norm_squared() > f32::EPSILON)
+                .collect();
+            let turning: f32 = legs.windows(2).map(|w| glm::angle(&w[0], &w[1])).sum();
+            turning <= tolerance
+        }
+    }
+}
+
+fn polygon_length(points: &[Vec3]) -> f32 {
+    points.windows(2).map(|w| glm::distance(&w[0], &w[1])).sum()
+}
+
+pub(crate) fn distance_to_segment(p: &Vec3, a: &Vec3, b: &Vec3) -> f32 {
+    let ab = b - a;
+    let len = ab.norm_squared();
+    if len <= f32::EPSILON {
+        return glm::distance(p, a);
+    }
+    let t = (glm::dot(&(p - a), &ab) / len).clamp(0.0, 1.0);
+    glm::distance(p, &(a + ab * t))
+}
 
 #[derive(Clone)]
 pub struct Bezier<const N: usize> {
@@ -12,26 +449,96 @@ pub struct Bezier<const N: usize> {
 impl<const N: usize> Bezier<N> {
     pub fn new(begin: Vec3, end: Vec3) -> Self {
         let mut ctrl_points = [Vec3::new(0.0, 0.0, 0.0); N];
-        for i in 0..N {
+        for (i, point) in ctrl_points.iter_mut().enumerate() {
             let d: f32 = i as f32 / (N - 1) as f32;
-            ctrl_points[i] = begin * (1.0 - d) + end * d;
+            *point = begin * (1.0 - d) + end * d;
         }
         Self { ctrl_points }
     }
 
     pub fn evaluate(&self, resolution: usize) -> PolyLine {
-        let mut curve_points = Vec::with_capacity(resolution);
-        let mut u: f32 = 0.0;
-        let epsilon = 1.0 / resolution as f32;
-        while u <= 1.0 {
-            curve_points.push(de_casteljau(u, &self.ctrl_points));
-            u += epsilon;
+        PolyLine {
+            points: parameters(0.0, 1.0, resolution)
+                .map(|u| de_casteljau(u, &self.ctrl_points))
+                .collect(),
+            line_strip: true,
         }
+    }
+
+    /// Polyline adapted to the shape of the curve: the curve is recursively split in
+    /// halves until the control polygon of every piece satisfies `flatness`.
+    pub fn tessellate(&self, flatness: Flatness) -> PolyLine {
+        let mut points = vec![self.ctrl_points[0]];
+        flatten(&self.ctrl_points, flatness, MAX_FLATTEN_DEPTH, &mut points);
         PolyLine {
-            points: curve_points,
+            points,
             line_strip: true,
         }
     }
+
+    /// Control points of the derivative curve of the given `order`.
+    pub fn derivative_points(&self, order: usize) -> Vec<Vec3> {
+        (0..order).fold(self.ctrl_points.to_vec(), |points, _| hodograph(&points))
+    }
+
+    pub fn split(&self, t: f32) -> (Bezier<N>, Bezier<N>) {
+        let (left, right) = subdivide(t, &self.ctrl_points);
+        (Bezier::from(to_array(left)), Bezier::from(to_array(right)))
+    }
+
+    /// Same curve with `K` control points, `K` must be at least `N`, checked at compile time.
+    pub fn elevate<const K: usize>(&self) -> Bezier<K> {
+        const { assert!(K >= N, "cannot elevate a Bezier to fewer control points") };
+        let points = (N..K).fold(self.ctrl_points.to_vec(), |points, _| {
+            elevate_degree(&points)
+        });
+        Bezier::from(to_array(points))
+    }
+
+    /// Approximation of the curve with `K` control points, along with an upper
+    /// bound of the distance between the two curves. `K` must be in `2..=N`,
+    /// checked at compile time.
+    pub fn reduce<const K: usize>(&self) -> (Bezier<K>, f32) {
+        const {
+            assert!(
+                K >= 2 && K <= N,
+                "a Bezier reduces to at least 2 and at most its control points"
+            )
+        };
+        let points = (K..N).fold(self.ctrl_points.to_vec(), |points, _| {
+            reduce_degree(&points)
+        });
+        let reduced = Bezier::from(to_array(points));
+        // the difference of the curves is bounded by the one of their control points
+        let error = reduced
+            .elevate::<N>()
+            .ctrl_points
+            .iter()
+            .zip(&self.ctrl_points)
+            .map(|(a, b)| glm::distance(a, b))
+            .fold(0.0, f32::max);
+        (reduced, error)
+    }
+}
+
+fn to_array<const N: usize>(points: Vec<Vec3>) -> [Vec3; N] {
+    points
+        .try_into()
+        .expect("unexpected number of control points")
+}
+
+impl<const N: usize> Curve for Bezier<N> {
+    fn point(&self, t: f32) -> Vec3 {
+        de_casteljau(t, &self.ctrl_points)
+    }
+
+    fn derivative(&self, t: f32, order: usize) -> Vec3 {
+        de_casteljau(t, &self.derivative_points(order))
+    }
+
+    fn control_polygon_length(&self) -> f32 {
+        polygon_length(&self.ctrl_points)
+    }
 }
 
 impl<const N: usize> From<[Vec3; N]> for Bezier<N> {
@@ -43,35 +550,739 @@ impl<const N: usize> From<[Vec3; N]> for Bezier<N> {
 }
 
 // PieceWiseBezier
+#[derive(Clone)]
 pub struct PiecewiseBezier<const N: usize> {
     pub ctrl_points: Vec<Vec3>,
 }
 
 impl<const N: usize> PiecewiseBezier<N> {
     pub fn new(begin: Vec3, end: Vec3) -> Self {
-        let mut ctrl_points = Vec::with_capacity(N);
-        for i in 0..N {
-            let d: f32 = i as f32 / (N - 1) as f32;
-            ctrl_points[i] = begin * (1.0 - d) + end * d;
+        Self {
+            ctrl_points: Bezier::<N>::new(begin, end).ctrl_points.to_vec(),
+        }
+    }
+
+    /// Samples `resolution` points, at least 2, evenly spaced over the global parameter.
+    pub fn evaluate(&self, resolution: usize) -> PolyLine {
+        let points = if self.segment_count() == 0 {
+            Vec::new()
+        } else {
+            parameters(0.0, 1.0, resolution.max(2) - 1)
+                .map(|t| self.point(t))
+                .collect()
+        };
+        PolyLine {
+            points,
+            line_strip: true,
+        }
+    }
+
+    /// Segment-wise adaptive tessellation, see [`Bezier::tessellate`].
+    pub fn tessellate(&self, flatness: Flatness) -> PolyLine {
+        let mut points: Vec<Vec3> = Vec::new();
+        for segment in self.ctrl_points.chunks_exact(N) {
+            if points.last() != Some(&segment[0]) {
+                points.push(segment[0]);
+            }
+            flatten(segment, flatness, MAX_FLATTEN_DEPTH, &mut points);
+        }
+        PolyLine {
+            points,
+            line_strip: true,
+        }
+    }
+}
+
+impl<const N: usize> PiecewiseBezier<N> {
+    pub fn segment_count(&self) -> usize {
+        self.ctrl_points.len() / N
+    }
+
+    pub fn segment(&self, index: usize) -> &[Vec3] {
+        &self.ctrl_points[index * N..(index + 1) * N]
+    }
+
+    /// Maps the global parameter `t` in `[0, 1]` to a segment index and its local parameter.
+    /// A curve without segments maps everything to `(0, 0.0)`.
+    pub fn locate(&self, t: f32) -> (usize, f32) {
+        let segments = self.segment_count();
+        let scaled = t.clamp(0.0, 1.0) * segments as f32;
+        let index = (scaled.floor() as usize).min(segments.saturating_sub(1));
+        (index, scaled - index as f32)
+    }
+
+    pub fn segments(&self) -> impl Iterator<Item = Bezier<N>> + '_ {
+        self.ctrl_points
+            .chunks_exact(N)
+            .map(|segment| Bezier::from(to_array(segment.to_vec())))
+    }
+
+    /// Splits the curve at the global parameter `t` into two piecewise curves.
+    pub fn split(&self, t: f32) -> (Self, Self) {
+        if self.segment_count() == 0 {
+            return (self.clone(), self.clone());
         }
+        let (index, u) = self.locate(t);
+        let (mut left, mut right) = (self.ctrl_points.clone(), self.ctrl_points.clone());
+        left.truncate(index * N);
+        right.drain(..(index + 1) * N);
+        if u > 0.0 || index == 0 {
+            let (head, tail) = subdivide(u, self.segment(index));
+            left.extend(head);
+            right.splice(0..0, tail);
+        } else {
+            right.splice(0..0, self.segment(index).iter().copied());
+        }
+        (Self { ctrl_points: left }, Self { ctrl_points: right })
+    }
+
+    /// Splits every segment at its local parameter `t`, doubling the number of segments.
+    pub fn split_segments(&self, t: f32) -> Self {
+        self.segments()
+            .flat_map(|segment| {
+                let (left, right) = segment.split(t);
+                [left, right]
+            })
+            .collect()
+    }
+
+    pub fn elevate<const K: usize>(&self) -> PiecewiseBezier<K> {
+        self.segments()
+            .map(|segment| segment.elevate::<K>())
+            .collect()
+    }
+
+    /// Segment-wise degree reduction, along with the largest error bound of the segments.
+    pub fn reduce<const K: usize>(&self) -> (PiecewiseBezier<K>, f32) {
+        let mut error: f32 = 0.0;
+        let curve = self
+            .segments()
+            .map(|segment| {
+                let (reduced, segment_error) = segment.reduce::<K>();
+                error = error.max(segment_error);
+                reduced
+            })
+            .collect();
+        (curve, error)
+    }
+}
+
+impl<const N: usize> FromIterator<Bezier<N>> for PiecewiseBezier<N> {
+    fn from_iter<I: IntoIterator<Item = Bezier<N>>>(segments: I) -> Self {
         Self {
+            ctrl_points: segments
+                .into_iter()
+                .flat_map(|segment| segment.ctrl_points)
+                .collect(),
+        }
+    }
+}
+
+// the segments share the global parameter range [0, 1] evenly, a curve without
+// segments is the single point at the origin
+impl<const N: usize> Curve for PiecewiseBezier<N> {
+    fn point(&self, t: f32) -> Vec3 {
+        if self.segment_count() == 0 {
+            return Vec3::zeros();
+        }
+        let (index, u) = self.locate(t);
+        de_casteljau(u, self.segment(index))
+    }
+
+    fn derivative(&self, t: f32, order: usize) -> Vec3 {
+        if self.segment_count() == 0 {
+            return Vec3::zeros();
+        }
+        let (index, u) = self.locate(t);
+        let points = (0..order).fold(self.segment(index).to_vec(), |points, _| hodograph(&points));
+        de_casteljau(u, &points) * (self.segment_count() as f32).powi(order as i32)
+    }
+
+    fn control_polygon_length(&self) -> f32 {
+        polygon_length(&self.ctrl_points)
+    }
+
+    fn breakpoints(&self) -> Vec<f32> {
+        let segments = self.segment_count().max(1);
+        (0..=segments).map(|i| i as f32 / segments as f32).collect()
+    }
+}
+
+pub(crate) fn validate_knots(
+    degree: usize,
+    ctrl_count: usize,
+    knots: &[f32],
+) -> Result<(), SplineError> {
+    if degree == 0 || ctrl_count <= degree {
+        return Err(SplineError::NotEnoughControlPoints);
+    }
+    let expected = ctrl_count + degree + 1;
+    if knots.len() != expected {
+        return Err(SplineError::InvalidKnotCount {
+            expected,
+            found: knots.len(),
+        });
+    }
+    if knots.windows(2).any(|w| w[0] > w[1]) {
+        return Err(SplineError::DecreasingKnots);
+    }
+    if knots[degree] >= knots[ctrl_count] {
+        return Err(SplineError::EmptyDomain);
+    }
+    Ok(())
+}
+
+fn distinct_knots(knots: &[f32], (start, end): (f32, f32)) -> Vec<f32> {
+    let mut distinct: Vec<f32> = knots
+        .iter()
+        .copied()
+        .filter(|&k| k >= start && k <= end)
+        .collect();
+    distinct.dedup();
+    distinct
+}
+
+// BSpline
+#[derive(Clone)]
+pub struct BSpline {
+    degree: usize,
+    ctrl_points: Vec<Vec3>,
+    knots: Vec<f32>,
+}
+
+impl BSpline {
+    pub fn new(
+        degree: usize,
+        ctrl_points: Vec<Vec3>,
+        knots: Vec<f32>,
+    ) -> Result<Self, SplineError> {
+        validate_knots(degree, ctrl_points.len(), &knots)?;
+        Ok(Self {
+            degree,
             ctrl_points,
+            knots,
+        })
+    }
+
+    /// Clamped B-spline with uniformly spaced interior knots over `[0, 1]`.
+    pub fn uniform(degree: usize, ctrl_points: Vec<Vec3>) -> Result<Self, SplineError> {
+        if degree == 0 || ctrl_points.len() <= degree {
+            return Err(SplineError::NotEnoughControlPoints);
         }
+        let spans = ctrl_points.len() - degree;
+        let mut knots = vec![0.0; degree + 1];
+        knots.extend((1..spans).map(|i| i as f32 / spans as f32));
+        knots.extend(vec![1.0; degree + 1]);
+        Self::new(degree, ctrl_points, knots)
+    }
+
+    pub fn degree(&self) -> usize {
+        self.degree
+    }
+
+    pub fn ctrl_points(&self) -> &[Vec3] {
+        &self.ctrl_points
+    }
+
+    pub fn knots(&self) -> &[f32] {
+        &self.knots
+    }
+
+    pub fn domain(&self) -> (f32, f32) {
+        (self.knots[self.degree], self.knots[self.ctrl_points.len()])
+    }
+
+    pub fn point(&self, t: f32) -> Vec3 {
+        de_boor(t, self.degree, &self.knots, &self.ctrl_points)
     }
 
     pub fn evaluate(&self, resolution: usize) -> PolyLine {
-        let mut curve_points = Vec::with_capacity(resolution);
-        let epsilon = 1.0 / (resolution as f32 / self.ctrl_points.len() as f32);
-        for i in (0..self.ctrl_points.len()).step_by(N) {
-            let mut u: f32 = 0.0;
-            while u <= 1.0 {
-                curve_points.push(de_casteljau(u, &self.ctrl_points[i..i + N]));
-                u += epsilon;
+        let (start, end) = self.domain();
+        PolyLine {
+            points: parameters(start, end, resolution.max(2) - 1)
+                .map(|t| self.point(t))
+                .collect(),
+            line_strip: true,
+        }
+    }
+
+    pub fn multiplicity(&self, t: f32) -> usize {
+        self.knots.iter().filter(|&&k| k == t).count()
+    }
+
+    /// Inserts the knot `t` `times` times without changing the shape of the curve (Boehm's algorithm).
+    pub fn insert_knot(&mut self, t: f32, times: usize) -> Result<(), SplineError> {
+        let (start, end) = self.domain();
+        if t < start || t > end {
+            return Err(SplineError::ParameterOutOfDomain);
+        }
+        if self.multiplicity(t) + times > self.degree + 1 {
+            return Err(SplineError::KnotMultiplicity);
+        }
+        let p = self.degree;
+        for _ in 0..times {
+            let k = find_span(t, p, &self.knots);
+            let mut points = Vec::with_capacity(self.ctrl_points.len() + 1);
+            points.extend_from_slice(&self.ctrl_points[..=k - p]);
+            for i in (k - p + 1)..=k {
+                let denom = self.knots[i + p] - self.knots[i];
+                let alpha = if denom == 0.0 {
+                    0.0
+                } else {
+                    (t - self.knots[i]) / denom
+                };
+                points.push(self.ctrl_points[i - 1] * (1.0 - alpha) + self.ctrl_points[i] * alpha);
+            }
+            points.extend_from_slice(&self.ctrl_points[k..]);
+            self.ctrl_points = points;
+            self.knots.insert(k + 1, t);
+        }
+        Ok(())
+    }
+
+    /// Splits the curve into Bezier segments by raising every knot of the domain to multiplicity `degree`.
+    pub fn to_piecewise_bezier<const N: usize>(&self) -> Result<PiecewiseBezier<N>, SplineError> {
+        if N != self.degree + 1 {
+            return Err(SplineError::DegreeMismatch);
+        }
+        let mut refined = self.clone();
+        for knot in distinct_knots(&self.knots, self.domain()) {
+            let multiplicity = refined.multiplicity(knot);
+            if multiplicity < self.degree {
+                refined.insert_knot(knot, self.degree - multiplicity)?;
+            }
+        }
+
+        let p = refined.degree;
+        let mut ctrl_points = Vec::new();
+        for i in p..refined.ctrl_points.len() {
+            if refined.knots[i] < refined.knots[i + 1] {
+                ctrl_points.extend_from_slice(&refined.ctrl_points[i - p..=i]);
+            }
+        }
+        Ok(PiecewiseBezier { ctrl_points })
+    }
+}
+
+impl Curve for BSpline {
+    fn domain(&self) -> (f32, f32) {
+        BSpline::domain(self)
+    }
+
+    fn point(&self, t: f32) -> Vec3 {
+        BSpline::point(self, t)
+    }
+
+    fn control_polygon_length(&self) -> f32 {
+        polygon_length(&self.ctrl_points)
+    }
+
+    /// Evaluated on the derivative B-spline, whose control points are the scaled
+    /// differences of the ones of the curve.
+    fn derivative(&self, t: f32, order: usize) -> Vec3 {
+        if order > self.degree {
+            return Vec3::zeros();
+        }
+        let mut degree = self.degree;
+        let mut points = self.ctrl_points.clone();
+        let mut knots = self.knots.clone();
+        for _ in 0..order {
+            points = points
+                .windows(2)
+                .enumerate()
+                .map(|(i, w)| {
+                    let denom = knots[i + degree + 1] - knots[i + 1];
+                    if denom == 0.0 {
+                        Vec3::zeros()
+                    } else {
+                        (w[1] - w[0]) * degree as f32 / denom
+                    }
+                })
+                .collect();
+            knots = knots[1..knots.len() - 1].to_vec();
+            degree -= 1;
+        }
+        de_boor(t, degree, &knots, &points)
+    }
+
+    fn breakpoints(&self) -> Vec<f32> {
+        distinct_knots(&self.knots, self.domain())
+    }
+}
+
+impl<const N: usize> TryFrom<&PiecewiseBezier<N>> for BSpline {
+    type Error = SplineError;
+
+    /// Segment `i` is mapped to the parameter range `[i, i + 1]`. Segments which do not
+    /// share their end points get a knot of full multiplicity so that the gap is preserved.
+    fn try_from(curve: &PiecewiseBezier<N>) -> Result<BSpline, SplineError> {
+        let degree = N.saturating_sub(1);
+        let mut ctrl_points: Vec<Vec3> = Vec::with_capacity(curve.ctrl_points.len());
+        let mut knots = vec![0.0; N];
+        for (i, segment) in curve.ctrl_points.chunks_exact(N.max(1)).enumerate() {
+            if i > 0 {
+                if ctrl_points.last() == segment.first() {
+                    knots.extend(vec![i as f32; degree]);
+                    ctrl_points.extend_from_slice(&segment[1..]);
+                    continue;
+                }
+                knots.extend(vec![i as f32; N]);
+            }
+            ctrl_points.extend_from_slice(segment);
+        }
+        knots.extend(vec![(curve.ctrl_points.len() / N.max(1)) as f32; N]);
+        BSpline::new(degree, ctrl_points, knots)
+    }
+}
+
+// CatmullRom
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CatmullRomParameterization {
+    Uniform,
+    Centripetal,
+    Chordal,
+}
+
+impl CatmullRomParameterization {
+    fn alpha(self) -> f32 {
+        match self {
+            Self::Uniform => 0.0,
+            Self::Centripetal => 0.5,
+            Self::Chordal => 1.0,
+        }
+    }
+}
+
+/// Interpolating spline going through every point of `points`.
+#[derive(Clone)]
+pub struct CatmullRom {
+    points: Vec<Vec3>,
+    parameterization: CatmullRomParameterization,
+    closed: bool,
+}
+
+impl CatmullRom {
+    pub fn new(
+        points: Vec<Vec3>,
+        parameterization: CatmullRomParameterization,
+        closed: bool,
+    ) -> Result<Self, SplineError> {
+        if points.len() < 2 {
+            return Err(SplineError::NotEnoughControlPoints);
+        }
+        Ok(Self {
+            points,
+            parameterization,
+            closed,
+        })
+    }
+
+    pub fn points(&self) -> &[Vec3] {
+        &self.points
+    }
+
+    pub fn parameterization(&self) -> CatmullRomParameterization {
+        self.parameterization
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.closed
+    }
+
+    /// Exact conversion to cubic Bezier segments, one per pair of consecutive points.
+    /// The ends of an open spline use mirrored phantom points.
+    pub fn to_piecewise_bezier(&self) -> PiecewiseBezier<4> {
+        let n = self.points.len();
+        let point = |i: isize| -> Vec3 {
+            if self.closed {
+                self.points[i.rem_euclid(n as isize) as usize]
+            } else if i < 0 {
+                self.points[0] * 2.0 - self.points[1]
+            } else if i as usize >= n {
+                self.points[n - 1] * 2.0 - self.points[n - 2]
+            } else {
+                self.points[i as usize]
+            }
+        };
+        let alpha = self.parameterization.alpha();
+        let interval = |a: Vec3, b: Vec3| {
+            let d = glm::distance(&a, &b).powf(alpha);
+            if d > f32::EPSILON {
+                d
+            } else {
+                1.0
             }
+        };
+
+        let segments = if self.closed { n } else { n - 1 };
+        let mut ctrl_points = Vec::with_capacity(segments * 4);
+        for i in 0..segments as isize {
+            let (p0, p1, p2, p3) = (point(i - 1), point(i), point(i + 1), point(i + 2));
+            let (d0, d1, d2) = (interval(p0, p1), interval(p1, p2), interval(p2, p3));
+            // tangents of the Barry-Goldman formulation, rescaled to the segment's [0, 1]
+            let m1 = ((p1 - p0) / d0 - (p2 - p0) / (d0 + d1) + (p2 - p1) / d1) * d1;
+            let m2 = ((p2 - p1) / d1 - (p3 - p1) / (d1 + d2) + (p3 - p2) / d2) * d1;
+            ctrl_points.extend([p1, p1 + m1 / 3.0, p2 - m2 / 3.0, p2]);
         }
+        PiecewiseBezier { ctrl_points }
+    }
+}
+
+impl From<&CatmullRom> for PiecewiseBezier<4> {
+    fn from(spline: &CatmullRom) -> PiecewiseBezier<4> {
+        spline.to_piecewise_bezier()
+    }
+}
+
+// Hermite
+/// Cubic Hermite spline through `points`, with the derivative at each point given by `tangents`.
+#[derive(Clone)]
+pub struct Hermite {
+    points: Vec<Vec3>,
+    tangents: Vec<Vec3>,
+}
+
+impl Hermite {
+    pub fn new(points: Vec<Vec3>, tangents: Vec<Vec3>) -> Result<Self, SplineError> {
+        if points.len() < 2 {
+            return Err(SplineError::NotEnoughControlPoints);
+        }
+        if tangents.len() != points.len() {
+            return Err(SplineError::TangentCountMismatch);
+        }
+        Ok(Self { points, tangents })
+    }
+
+    pub fn points(&self) -> &[Vec3] {
+        &self.points
+    }
+
+    pub fn tangents(&self) -> &[Vec3] {
+        &self.tangents
+    }
+
+    /// Exact conversion to cubic Bezier segments, each tangent being relative to the
+    /// `[0, 1]` parameter range of its segments.
+    pub fn to_piecewise_bezier(&self) -> PiecewiseBezier<4> {
+        let mut ctrl_points = Vec::with_capacity((self.points.len() - 1) * 4);
+        for i in 0..self.points.len() - 1 {
+            let (p0, p1) = (self.points[i], self.points[i + 1]);
+            let (t0, t1) = (self.tangents[i], self.tangents[i + 1]);
+            ctrl_points.extend([p0, p0 + t0 / 3.0, p1 - t1 / 3.0, p1]);
+        }
+        PiecewiseBezier { ctrl_points }
+    }
+}
+
+impl From<&Hermite> for PiecewiseBezier<4> {
+    fn from(spline: &Hermite) -> PiecewiseBezier<4> {
+        spline.to_piecewise_bezier()
+    }
+}
+
+// NurbsCurve
+#[derive(Clone)]
+pub struct NurbsCurve {
+    degree: usize,
+    ctrl_points: Vec<Vec3>,
+    weights: Vec<f32>,
+    knots: Vec<f32>,
+}
+
+impl NurbsCurve {
+    pub fn new(
+        degree: usize,
+        ctrl_points: Vec<Vec3>,
+        weights: Vec<f32>,
+        knots: Vec<f32>,
+    ) -> Result<Self, SplineError> {
+        if weights.len() != ctrl_points.len() || weights.iter().any(|&w| w <= 0.0) {
+            return Err(SplineError::InvalidWeights);
+        }
+        let spline = BSpline::new(degree, ctrl_points, knots)?;
+        Ok(Self {
+            degree: spline.degree,
+            ctrl_points: spline.ctrl_points,
+            weights,
+            knots: spline.knots,
+        })
+    }
+
+    /// Rational quadratic Bezier segment. A `weight` below, equal to or above 1
+    /// gives an elliptic, parabolic or hyperbolic arc respectively.
+    pub fn conic(start: Vec3, control: Vec3, end: Vec3, weight: f32) -> Result<Self, SplineError> {
+        Self::new(
+            2,
+            vec![start, control, end],
+            vec![1.0, weight, 1.0],
+            vec![0.0, 0.0, 0.0, 1.0, 1.0, 1.0],
+        )
+    }
+
+    /// Exact circular arc in the plane spanned by the unit vectors `x_axis` and `y_axis`,
+    /// angles are in radians.
+    pub fn arc(
+        center: Vec3,
+        x_axis: Vec3,
+        y_axis: Vec3,
+        radius: f32,
+        start_angle: f32,
+        end_angle: f32,
+    ) -> Result<Self, SplineError> {
+        let mut sweep = end_angle - start_angle;
+        if sweep <= 0.0 {
+            sweep += TAU;
+        }
+        let arcs = ((sweep / FRAC_PI_2).ceil() as usize).clamp(1, 4);
+        let step = sweep / arcs as f32;
+        let middle_weight = (step / 2.0).cos();
+        let on_circle =
+            |angle: f32, r: f32| center + (x_axis * angle.cos() + y_axis * angle.sin()) * r;
+
+        let mut ctrl_points = vec![on_circle(start_angle, radius)];
+        let mut weights = vec![1.0];
+        let mut knots = vec![0.0; 3];
+        for i in 1..=arcs {
+            let angle = start_angle + step * i as f32;
+            ctrl_points.push(on_circle(angle - step / 2.0, radius / middle_weight));
+            ctrl_points.push(on_circle(angle, radius));
+            weights.extend([middle_weight, 1.0]);
+            if i < arcs {
+                let knot = i as f32 / arcs as f32;
+                knots.extend([knot, knot]);
+            }
+        }
+        knots.extend([1.0; 3]);
+        Self::new(2, ctrl_points, weights, knots)
+    }
+
+    pub fn circle(center: Vec3, x_axis: Vec3, y_axis: Vec3, radius: f32) -> Self {
+        Self::arc(center, x_axis, y_axis, radius, 0.0, TAU)
+            .expect("a full circle is always a valid NURBS curve")
+    }
+
+    pub fn degree(&self) -> usize {
+        self.degree
+    }
+
+    pub fn ctrl_points(&self) -> &[Vec3] {
+        &self.ctrl_points
+    }
+
+    pub fn weights(&self) -> &[f32] {
+        &self.weights
+    }
+
+    pub fn knots(&self) -> &[f32] {
+        &self.knots
+    }
+
+    pub fn domain(&self) -> (f32, f32) {
+        (self.knots[self.degree], self.knots[self.ctrl_points.len()])
+    }
+
+    pub fn point(&self, t: f32) -> Vec3 {
+        self.derivatives(t, 0)[0]
+    }
+
+    /// Position followed by the derivatives of the curve at `t`, up to `order`.
+    pub fn derivatives(&self, t: f32, order: usize) -> Vec<Vec3> {
+        let p = self.degree;
+        let span = find_span(t, p, &self.knots);
+        let basis = basis_functions_derivatives(span, t, p, &self.knots, order);
+
+        let homogeneous: Vec<Vec4> = basis
+            .iter()
+            .map(|ders| {
+                ders.iter().enumerate().fold(Vec4::zeros(), |acc, (j, &n)| {
+                    let i = span - p + j;
+                    acc + homogeneous(self.ctrl_points[i], self.weights[i]) * n
+                })
+            })
+            .collect();
+        rational_derivatives(&homogeneous)
+    }
+
+    pub fn evaluate(&self, resolution: usize) -> PolyLine {
+        let (start, end) = self.domain();
         PolyLine {
-            points: curve_points,
+            points: parameters(start, end, resolution.max(2) - 1)
+                .map(|t| self.point(t))
+                .collect(),
             line_strip: true,
         }
     }
 }
+
+impl Curve for NurbsCurve {
+    fn domain(&self) -> (f32, f32) {
+        NurbsCurve::domain(self)
+    }
+
+    fn point(&self, t: f32) -> Vec3 {
+        NurbsCurve::point(self, t)
+    }
+
+    fn control_polygon_length(&self) -> f32 {
+        polygon_length(&self.ctrl_points)
+    }
+
+    fn derivative(&self, t: f32, order: usize) -> Vec3 {
+        self.derivatives(t, order)[order]
+    }
+
+    fn breakpoints(&self) -> Vec<f32> {
+        distinct_knots(&self.knots, self.domain())
+    }
+}
+
+impl From<&BSpline> for NurbsCurve {
+    fn from(spline: &BSpline) -> NurbsCurve {
+        NurbsCurve {
+            degree: spline.degree,
+            ctrl_points: spline.ctrl_points.clone(),
+            weights: vec![1.0; spline.ctrl_points.len()],
+            knots: spline.knots.clone(),
+        }
+    }
+}
+
+impl<const N: usize> From<&Bezier<N>> for NurbsCurve {
+    fn from(curve: &Bezier<N>) -> NurbsCurve {
+        let mut knots = vec![0.0; N];
+        knots.extend([1.0; N]);
+        NurbsCurve {
+            degree: N - 1,
+            ctrl_points: curve.ctrl_points.to_vec(),
+            weights: vec![1.0; N],
+            knots,
+        }
+    }
+}
+
+impl<const N: usize> TryFrom<&PiecewiseBezier<N>> for NurbsCurve {
+    type Error = SplineError;
+
+    fn try_from(curve: &PiecewiseBezier<N>) -> Result<NurbsCurve, SplineError> {
+        Ok(NurbsCurve::from(&BSpline::try_from(curve)?))
+    }
+}
+
+pub(crate) fn homogeneous(point: Vec3, weight: f32) -> Vec4 {
+    Vec4::new(point.x * weight, point.y * weight, point.z * weight, weight)
+}
+
+/// Turns the derivatives of a curve in homogeneous coordinates `(w * P, w)` into
+/// the derivatives of its projection, `result[0]` being the position.
+pub(crate) fn rational_derivatives(homogeneous: &[Vec4]) -> Vec<Vec3> {
+    let mut ders: Vec<Vec3> = Vec::with_capacity(homogeneous.len());
+    let w = homogeneous[0].w;
+    for k in 0..homogeneous.len() {
+        let mut v = homogeneous[k].xyz();
+        for i in 1..=k {
+            v -= ders[k - i] * (binomial(k, i) * homogeneous[i].w);
+        }
+        ders.push(v / w);
+    }
+    ders
+}
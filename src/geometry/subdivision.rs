@@ -0,0 +1,359 @@
+/*
+* SPDX-License-Identifier: MIT
+*/
+
+use std::collections::HashMap;
+use std::f32::consts::PI;
+
+use nalgebra_glm as glm;
+
+use super::half_edge::{HalfEdgeError, HalfEdgeMesh};
+use super::primitives::{Mesh, Vertex};
+use super::processing::NormalWeighting;
+use super::{Vec2, Vec3};
+
+/// Sharpness of a tagged edge. The edge follows the sharp rules for `sharpness` levels
+/// and blends towards the smooth rules over the last fractional level, an infinite
+/// sharpness keeps it sharp forever.
+#[derive(Clone, Copy, Debug)]
+pub struct Crease {
+    pub edge: (u32, u32),
+    pub sharpness: f32,
+}
+
+/// Coarse polygon mesh defining a subdivision surface. Boundary edges are always sharp
+/// and `corners` are vertices that never move.
+/// The subdivided meshes have smooth normals, [`Mesh::compute_normals`] with a crease
+/// angle splits them along sharp creases.
+#[derive(Clone, Debug)]
+pub struct ControlCage {
+    pub vertices: Vec<Vertex>,
+    pub faces: Vec<Vec<u32>>,
+    pub creases: Vec<Crease>,
+    pub corners: Vec<u32>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Scheme {
+    CatmullClark,
+    Loop,
+}
+
+fn edge_key(a: u32, b: u32) -> (u32, u32) {
+    (a.min(b), a.max(b))
+}
+
+struct Edge {
+    ends: (u32, u32),
+    faces: Vec<usize>,
+    sharpness: f32,
+}
+
+/// One level of the subdivision, vertex attributes other than the position and uv
+/// are rebuilt at the end.
+struct Level {
+    positions: Vec<Vec3>,
+    uvs: Vec<Vec2>,
+    faces: Vec<Vec<u32>>,
+    creases: HashMap<(u32, u32), f32>,
+    corners: Vec<bool>,
+}
+
+impl Level {
+    fn edges(&self) -> (Vec<Edge>, HashMap<(u32, u32), usize>) {
+        let mut edges: Vec<Edge> = Vec::new();
+        let mut indices = HashMap::new();
+        for (face, polygon) in self.faces.iter().enumerate() {
+            for (k, &a) in polygon.iter().enumerate() {
+                let b = polygon[(k + 1) % polygon.len()];
+                let key = edge_key(a, b);
+                let index = *indices.entry(key).or_insert_with(|| {
+                    edges.push(Edge {
+                        ends: key,
+                        faces: Vec::new(),
+                        sharpness: self.creases.get(&key).copied().unwrap_or(0.0),
+                    });
+                    edges.len() - 1
+                });
+                edges[index].faces.push(face);
+            }
+        }
+        for edge in edges.iter_mut() {
+            if edge.faces.len() < 2 {
+                edge.sharpness = f32::INFINITY;
+            }
+        }
+        (edges, indices)
+    }
+
+    fn subdivide(&self, scheme: Scheme) -> Level {
+        let (edges, edge_indices) = self.edges();
+        let vertex_count = self.positions.len();
+
+        let mut vertex_edges = vec![Vec::new(); vertex_count];
+        for (index, edge) in edges.iter().enumerate() {
+            vertex_edges[edge.ends.0 as usize].push(index);
+            vertex_edges[edge.ends.1 as usize].push(index);
+        }
+        let mut vertex_faces = vec![Vec::new(); vertex_count];
+        for (face, polygon) in self.faces.iter().enumerate() {
+            for &vertex in polygon {
+                vertex_faces[vertex as usize].push(face);
+            }
+        }
+
+        let face_points: Vec<Vec3> = self
+            .faces
+            .iter()
+            .map(|polygon| average(polygon.iter().map(|&v| self.positions[v as usize])))
+            .collect();
+        let face_uvs: Vec<Vec2> = self
+            .faces
+            .iter()
+            .map(|polygon| {
+                let sum: Vec2 = polygon.iter().map(|&v| self.uvs[v as usize]).sum();
+                sum / polygon.len() as f32
+            })
+            .collect();
+
+        let edge_points: Vec<Vec3> = edges
+            .iter()
+            .map(|edge| {
+                let (a, b) = (
+                    self.positions[edge.ends.0 as usize],
+                    self.positions[edge.ends.1 as usize],
+                );
+                let sharp = (a + b) * 0.5;
+                if edge.sharpness >= 1.0 {
+                    return sharp;
+                }
+                let smooth = match scheme {
+                    Scheme::CatmullClark => {
+                        (a + b + face_points[edge.faces[0]] + face_points[edge.faces[1]]) * 0.25
+                    }
+                    Scheme::Loop => {
+                        let opposite = |face: usize| {
+                            let polygon = &self.faces[face];
+                            let vertex = polygon
+                                .iter()
+                                .find(|&&v| v != edge.ends.0 && v != edge.ends.1)
+                                .unwrap();
+                            self.positions[*vertex as usize]
+                        };
+                        (a + b) * 0.375
+                            + (opposite(edge.faces[0]) + opposite(edge.faces[1])) * 0.125
+                    }
+                };
+                glm::lerp(&smooth, &sharp, edge.sharpness)
+            })
+            .collect();
+
+        let vertex_points: Vec<Vec3> = (0..vertex_count)
+            .map(|vertex| {
+                let position = self.positions[vertex];
+                let incident = &vertex_edges[vertex];
+                if incident.is_empty() {
+                    return position;
+                }
+                let other = |edge: &Edge| {
+                    if edge.ends.0 as usize == vertex {
+                        edge.ends.1
+                    } else {
+                        edge.ends.0
+                    }
+                };
+                let creased: Vec<&Edge> = incident
+                    .iter()
+                    .map(|&e| &edges[e])
+                    .filter(|edge| edge.sharpness > 0.0)
+                    .collect();
+
+                let smooth = || match scheme {
+                    Scheme::CatmullClark => {
+                        let n = incident.len() as f32;
+                        let faces = average(vertex_faces[vertex].iter().map(|&f| face_points[f]));
+                        let midpoints = average(incident.iter().map(|&e| {
+                            (position + self.positions[other(&edges[e]) as usize]) * 0.5
+                        }));
+                        (faces + midpoints * 2.0 + position * (n - 3.0)) / n
+                    }
+                    Scheme::Loop => {
+                        let n = incident.len() as f32;
+                        let beta = (0.625 - (0.375 + 0.25 * (2.0 * PI / n).cos()).powi(2)) / n;
+                        let ring: Vec3 = incident
+                            .iter()
+                            .map(|&e| self.positions[other(&edges[e]) as usize])
+                            .sum();
+                        position * (1.0 - n * beta) + ring * beta
+                    }
+                };
+
+                if self.corners[vertex] || creased.len() > 2 {
+                    return position;
+                }
+                if creased.len() < 2 {
+                    return smooth();
+                }
+                let crease = (position * 6.0
+                    + self.positions[other(creased[0]) as usize]
+                    + self.positions[other(creased[1]) as usize])
+                    / 8.0;
+                let sharpness = (creased[0].sharpness + creased[1].sharpness) * 0.5;
+                if sharpness >= 1.0 {
+                    crease
+                } else {
+                    glm::lerp(&smooth(), &crease, sharpness)
+                }
+            })
+            .collect();
+
+        // new vertices: the old vertices, then the edge points, then the face points
+        let edge_offset = vertex_count as u32;
+        let face_offset = edge_offset + edges.len() as u32;
+        let edge_vertex = |a: u32, b: u32| edge_offset + edge_indices[&edge_key(a, b)] as u32;
+
+        let mut positions = vertex_points;
+        positions.extend(edge_points);
+        let mut uvs = self.uvs.clone();
+        uvs.extend(
+            edges.iter().map(|edge| {
+                (self.uvs[edge.ends.0 as usize] + self.uvs[edge.ends.1 as usize]) * 0.5
+            }),
+        );
+
+        let mut faces = Vec::new();
+        for (face, polygon) in self.faces.iter().enumerate() {
+            let count = polygon.len();
+            match scheme {
+                Scheme::CatmullClark => {
+                    let center = face_offset + face as u32;
+                    for k in 0..count {
+                        let (prev, vertex, next) = (
+                            polygon[(k + count - 1) % count],
+                            polygon[k],
+                            polygon[(k + 1) % count],
+                        );
+                        faces.push(vec![
+                            vertex,
+                            edge_vertex(vertex, next),
+                            center,
+                            edge_vertex(prev, vertex),
+                        ]);
+                    }
+                }
+                Scheme::Loop => {
+                    let mids: Vec<u32> = (0..3)
+                        .map(|k| edge_vertex(polygon[k], polygon[(k + 1) % 3]))
+                        .collect();
+                    for k in 0..3 {
+                        faces.push(vec![polygon[k], mids[k], mids[(k + 2) % 3]]);
+                    }
+                    faces.push(mids);
+                }
+            }
+        }
+        if scheme == Scheme::CatmullClark {
+            positions.extend(face_points);
+            uvs.extend(face_uvs);
+        }
+
+        let mut creases = HashMap::new();
+        for (index, edge) in edges.iter().enumerate() {
+            if edge.sharpness > 1.0 && edge.faces.len() == 2 {
+                let middle = edge_offset + index as u32;
+                creases.insert(edge_key(edge.ends.0, middle), edge.sharpness - 1.0);
+                creases.insert(edge_key(middle, edge.ends.1), edge.sharpness - 1.0);
+            }
+        }
+        let mut corners = self.corners.clone();
+        corners.resize(positions.len(), false);
+
+        Level {
+            positions,
+            uvs,
+            faces,
+            creases,
+            corners,
+        }
+    }
+}
+
+fn average(points: impl Iterator<Item = Vec3>) -> Vec3 {
+    let (sum, count) = points.fold((Vec3::zeros(), 0), |(sum, count), p| (sum + p, count + 1));
+    sum / count as f32
+}
+
+impl ControlCage {
+    pub fn new(vertices: Vec<Vertex>, faces: Vec<Vec<u32>>) -> Self {
+        Self {
+            vertices,
+            faces,
+            creases: Vec::new(),
+            corners: Vec::new(),
+        }
+    }
+
+    pub fn from_mesh(mesh: &Mesh) -> Self {
+        let faces = mesh.indices.chunks_exact(3).map(|t| t.to_vec()).collect();
+        Self::new(mesh.vertices.clone(), faces)
+    }
+
+    /// Subdivides `levels` times with the Catmull-Clark rules, every face becoming quads.
+    pub fn catmull_clark(&self, levels: usize) -> Result<Mesh, HalfEdgeError> {
+        self.subdivide(Scheme::CatmullClark, levels)
+    }
+
+    /// Subdivides `levels` times with the Loop rules, the cage must be made of triangles.
+    pub fn loop_subdivision(&self, levels: usize) -> Result<Mesh, HalfEdgeError> {
+        if self.faces.iter().any(|face| face.len() != 3) {
+            return Err(HalfEdgeError::NotTriangle);
+        }
+        self.subdivide(Scheme::Loop, levels)
+    }
+
+    fn subdivide(&self, scheme: Scheme, levels: usize) -> Result<Mesh, HalfEdgeError> {
+        // rejects non manifold cages, which have no well defined limit surface
+        HalfEdgeMesh::from_polygons(self.vertices.clone(), &self.faces)?;
+
+        let mut corners = vec![false; self.vertices.len()];
+        for &corner in &self.corners {
+            if let Some(corner) = corners.get_mut(corner as usize) {
+                *corner = true;
+            }
+        }
+        let mut level = Level {
+            positions: self.vertices.iter().map(|v| v.position).collect(),
+            uvs: self.vertices.iter().map(|v| v.uv).collect(),
+            faces: self.faces.clone(),
+            creases: self
+                .creases
+                .iter()
+                .map(|crease| (edge_key(crease.edge.0, crease.edge.1), crease.sharpness))
+                .collect(),
+            corners,
+        };
+        for _ in 0..levels {
+            level = level.subdivide(scheme);
+        }
+
+        let vertices = level
+            .positions
+            .iter()
+            .zip(&level.uvs)
+            .map(|(&position, &uv)| Vertex {
+                position,
+                normal: Vec3::zeros(),
+                uv,
+            })
+            .collect();
+        let mut indices = Vec::new();
+        for polygon in &level.faces {
+            for k in 1..polygon.len() - 1 {
+                indices.extend([polygon[0], polygon[k], polygon[k + 1]]);
+            }
+        }
+        let mut mesh = Mesh { vertices, indices };
+        mesh.compute_normals(NormalWeighting::Angle, PI);
+        Ok(mesh)
+    }
+}
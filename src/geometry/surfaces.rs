@@ -4,9 +4,18 @@
 
 use nalgebra_glm as glm;
 
-use super::{algorithms::de_casteljau, Vec3, Vec2};
+use super::{
+    algorithms::{
+        basis_functions_derivatives, de_casteljau, find_span, hodograph, solve_linear_system,
+        subdivide,
+    },
+    splines::{homogeneous, validate_knots, NurbsCurve, SplineError},
+    Vec2, Vec3, Vec4,
+};
+
+use super::bounds::Aabb;
+use super::primitives::{Mesh, Ray, Vertex};
 
-use super::primitives::{Mesh, Vertex};
 pub struct BezierSurface<const M: usize, const N: usize> {
     ctrl_grid: [[Vec3; N]; M],
     mesh: Mesh,
@@ -27,91 +36,929 @@ impl<const M: usize, const N: usize> BezierSurface<M, N> {
         surface
     }
 
+    pub fn ctrl_grid(&self) -> &[[Vec3; N]; M] {
+        &self.ctrl_grid
+    }
+
+    pub fn mesh(&self) -> &Mesh {
+        &self.mesh
+    }
+
+    pub fn into_mesh(self) -> Mesh {
+        self.mesh
+    }
+
     pub fn set_epsilon(&mut self, edges: usize) {
         self.mesh_edges = edges;
     }
 
-    fn evaluate(&mut self) {
-        let mut q_points = vec![Vec3::new(0.0, 0.0, 0.0); M * self.mesh_edges];
+    pub fn point(&self, u: f32, v: f32) -> Vec3 {
+        let rows: Vec<Vec3> = self
+            .ctrl_grid
+            .iter()
+            .map(|row| de_casteljau(v, row))
+            .collect();
+        de_casteljau(u, &rows)
+    }
+
+    /// Position and first partial derivatives `(S, dS/du, dS/dv)` at `(u, v)`.
+    pub fn partial_derivatives(&self, u: f32, v: f32) -> (Vec3, Vec3, Vec3) {
+        let rows: Vec<Vec3> = self
+            .ctrl_grid
+            .iter()
+            .map(|row| de_casteljau(v, row))
+            .collect();
+        let columns: Vec<Vec3> = (0..N)
+            .map(|j| {
+                let column: Vec<Vec3> = self.ctrl_grid.iter().map(|row| row[j]).collect();
+                de_casteljau(u, &column)
+            })
+            .collect();
+        (
+            de_casteljau(u, &rows),
+            de_casteljau(u, &hodograph(&rows)),
+            de_casteljau(v, &hodograph(&columns)),
+        )
+    }
 
-        for i in 0..M {
-            for j in 0..self.mesh_edges {
-                let v = j as f32 / (self.mesh_edges as f32 - 1.0);
-                q_points[j * M + i] = de_casteljau(v, &self.ctrl_grid[i]);
+    /// Unit normal `dS/du x dS/dv`. Where the patch is degenerate, like at the apex of
+    /// the teapot lid where a whole edge collapses to a point, the normal is taken
+    /// slightly inside the patch instead.
+    pub fn normal(&self, u: f32, v: f32) -> Vec3 {
+        let (_, su, sv) = self.partial_derivatives(u, v);
+        let n = glm::cross(&su, &sv);
+        if !is_degenerate(&n, &su, &sv) {
+            return glm::normalize(&n);
+        }
+        let (_, su, sv) = self.partial_derivatives(nudge(u, 0.0, 1.0), nudge(v, 0.0, 1.0));
+        let n = glm::cross(&su, &sv);
+        if n.norm_squared() > 0.0 {
+            glm::normalize(&n)
+        } else {
+            n
+        }
+    }
+
+    /// Closest intersection of `ray` with the patch. The control grid is subdivided while its
+    /// bounding box is hit by the ray, then every small enough piece seeds Newton iterations
+    /// solving `S(u, v) = origin + t * direction`.
+    pub fn intersect_ray(&self, ray: &Ray) -> Option<PatchHit> {
+        let grid: Vec<Vec<Vec3>> = self.ctrl_grid.iter().map(|row| row.to_vec()).collect();
+        let (min, max) = bounds(grid.iter().flatten());
+        let min_size = glm::distance(&min, &max) / RAY_SUBDIVISION_RATIO;
+        let mut seeds = Vec::new();
+        collect_ray_seeds(ray, grid, ((0.0, 1.0), (0.0, 1.0)), min_size, 0, &mut seeds);
+
+        let mut closest: Option<PatchHit> = None;
+        for (u, v) in seeds {
+            if let Some(hit) = self.refine_ray_hit(ray, u, v) {
+                if closest.is_none_or(|c| hit.t < c.t) {
+                    closest = Some(hit);
+                }
             }
         }
+        closest
+    }
 
-        for i in 0..(q_points.len() / M) {
-            for j in 0..self.mesh_edges {
-                let u = j as f32 / (self.mesh_edges as f32 - 1.0);
+    fn refine_ray_hit(&self, ray: &Ray, mut u: f32, mut v: f32) -> Option<PatchHit> {
+        let mut t = glm::dot(&(self.point(u, v) - ray.origin), &ray.direction);
+        for _ in 0..MAX_RAY_NEWTON_ITERATIONS {
+            let (s, su, sv) = self.partial_derivatives(u, v);
+            let residual = s - ray.at(t);
+            if residual.norm() <= RAY_HIT_TOLERANCE * (1.0 + t.abs()) {
+                let inside = |x: f32| (-RAY_HIT_TOLERANCE..=1.0 + RAY_HIT_TOLERANCE).contains(&x);
+                if !inside(u) || !inside(v) || t < 0.0 {
+                    return None;
+                }
+                let (u, v) = (u.clamp(0.0, 1.0), v.clamp(0.0, 1.0));
+                return Some(PatchHit {
+                    u,
+                    v,
+                    t,
+                    point: ray.at(t),
+                    normal: self.normal(u, v),
+                });
+            }
+            let jacobian = glm::Mat3::from_columns(&[su, sv, -ray.direction]);
+            let step = jacobian.try_inverse()? * residual;
+            u = (u - step.x).clamp(-0.5, 1.5);
+            v = (v - step.y).clamp(-0.5, 1.5);
+            t -= step.z;
+        }
+        None
+    }
 
+    /// Tessellates the patch with as few triangles as `tolerance` allows. The sampling of
+    /// each boundary only depends on the control points of that boundary, so patches
+    /// sharing an edge produce the exact same vertices along it and the mesh stays crack-free.
+    pub fn tessellate_adaptive(&self, tolerance: SurfaceTolerance) -> Mesh {
+        let points: Vec<Vec3> = self.ctrl_grid.iter().flatten().copied().collect();
+        let world_tolerance = tolerance.world(&points);
+        let rows: Vec<Vec<Vec3>> = self.ctrl_grid.iter().map(|row| row.to_vec()).collect();
+        let columns: Vec<Vec<Vec3>> = (0..N)
+            .map(|j| self.ctrl_grid.iter().map(|row| row[j]).collect())
+            .collect();
+        let steps_u = curve_steps(&columns, world_tolerance / 2.0);
+        let steps_v = curve_steps(&rows, world_tolerance / 2.0);
+
+        let vertex = |u: f32, v: f32, position: Vec3| Vertex {
+            position,
+            normal: self.normal(u, v),
+            uv: Vec2::new(u, v),
+        };
+
+        if steps_u == 1 && steps_v == 1 {
+            let corners = [(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)];
+            return Mesh {
+                vertices: corners
+                    .iter()
+                    .map(|&(u, v)| vertex(u, v, self.point(u, v)))
+                    .collect(),
+                indices: vec![0, 1, 2, 0, 2, 3],
+            };
+        }
+
+        // the boundary is walked counter-clockwise in parameter space, `s` in [0, 4)
+        // being the position along it, one unit per side
+        let reversed = |curve: &[Vec3]| curve.iter().rev().copied().collect::<Vec<Vec3>>();
+        let sides = [
+            columns[0].clone(),
+            rows[M - 1].clone(),
+            reversed(&columns[N - 1]),
+            reversed(&rows[0]),
+        ];
+        let mut vertices = Vec::new();
+        let mut outer = Vec::new();
+        for (side, curve) in sides.iter().enumerate() {
+            let steps = curve_steps(std::slice::from_ref(curve), tolerance.world(curve));
+            for (k, position) in edge_points(curve, steps)
+                .into_iter()
+                .enumerate()
+                .take(steps)
+            {
+                let d = k as f32 / steps as f32;
+                let (u, v) = match side {
+                    0 => (d, 0.0),
+                    1 => (1.0, d),
+                    2 => (1.0 - d, 1.0),
+                    _ => (0.0, 1.0 - d),
+                };
+                outer.push((vertices.len() as u32, side as f32 + d));
+                vertices.push(vertex(u, v, position));
+            }
+        }
+
+        // inner grid, one step away from the boundary on every side
+        let (steps_u, steps_v) = (steps_u.max(3), steps_v.max(3));
+        let (columns, rows) = (steps_u - 1, steps_v - 1);
+        let first_inner = vertices.len() as u32;
+        for j in 1..steps_v {
+            let v = j as f32 / steps_v as f32;
+            for i in 1..steps_u {
+                let u = i as f32 / steps_u as f32;
+                vertices.push(vertex(u, v, self.point(u, v)));
+            }
+        }
+        let mut indices: Vec<u32> = grid_indices(columns, rows)
+            .into_iter()
+            .map(|index| index + first_inner)
+            .collect();
+
+        let inner = |i: usize, j: usize| first_inner + (j * columns + i) as u32;
+        let (last_column, last_row) = ((columns - 1) as f32, (rows - 1) as f32);
+        let mut ring = Vec::with_capacity(2 * (columns + rows));
+        ring.extend((0..columns - 1).map(|i| (inner(i, 0), i as f32 / last_column)));
+        ring.extend((0..rows - 1).map(|j| (inner(columns - 1, j), 1.0 + j as f32 / last_row)));
+        ring.extend((1..columns).rev().map(|i| {
+            (
+                inner(i, rows - 1),
+                2.0 + (columns - 1 - i) as f32 / last_column,
+            )
+        }));
+        ring.extend(
+            (1..rows)
+                .rev()
+                .map(|j| (inner(0, j), 3.0 + (rows - 1 - j) as f32 / last_row)),
+        );
+
+        indices.extend(zip_loops(&outer, &ring));
+        Mesh { vertices, indices }
+    }
+
+    fn evaluate(&mut self) {
+        let edges = self.mesh_edges;
+        self.mesh.vertices = Vec::with_capacity(edges * edges);
+        for i in 0..edges {
+            let v = i as f32 / (edges as f32 - 1.0);
+            for j in 0..edges {
+                let u = j as f32 / (edges as f32 - 1.0);
                 let vertex = Vertex {
-                    position: de_casteljau(u, &q_points[i * M..(i + 1) * M]),
-                    normal: Vec3::new(0.0, 0.0, 0.0),
-                    uv: Vec2::new(1.0, 1.0),
+                    position: self.point(u, v),
+                    normal: self.normal(u, v),
+                    uv: Vec2::new(u, v),
                 };
                 self.mesh.vertices.push(vertex);
             }
         }
-        self.calculate_indices();
-        // TODO : calculate normals with bezier derivative
-        self.calculate_normals();
-    }
-
-    fn calculate_indices(&mut self) {
-        self.mesh.indices = Vec::with_capacity(self.mesh_edges.pow(2));
-        for i in 0..(self.mesh_edges as u32 - 1) {
-            for j in 0..(self.mesh_edges as u32 - 1) {
-                //first triangle
-                self.mesh.indices.push(i * self.mesh_edges as u32 + j);
-                self.mesh.indices.push(i * self.mesh_edges as u32 + j + 1);
-                self.mesh
-                    .indices
-                    .push(i * self.mesh_edges as u32 + j + self.mesh_edges as u32);
-                //second triangle
-                self.mesh.indices.push(i * self.mesh_edges as u32 + j + 1);
-                self.mesh
-                    .indices
-                    .push(i * self.mesh_edges as u32 + j + self.mesh_edges as u32);
-                self.mesh
-                    .indices
-                    .push(i * self.mesh_edges as u32 + j + self.mesh_edges as u32 + 1);
+        self.mesh.indices = grid_indices(edges, edges);
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct PatchHit {
+    pub u: f32,
+    pub v: f32,
+    /// Distance along the ray.
+    pub t: f32,
+    pub point: Vec3,
+    pub normal: Vec3,
+}
+
+const RAY_SUBDIVISION_RATIO: f32 = 64.0;
+const MAX_RAY_SUBDIVISION_DEPTH: usize = 10;
+const MAX_RAY_NEWTON_ITERATIONS: usize = 16;
+const RAY_HIT_TOLERANCE: f32 = 1e-5;
+
+fn bounds<'a>(points: impl Iterator<Item = &'a Vec3>) -> (Vec3, Vec3) {
+    points.fold(
+        (Vec3::repeat(f32::INFINITY), Vec3::repeat(f32::NEG_INFINITY)),
+        |(min, max), p| (glm::min2(&min, p), glm::max2(&max, p)),
+    )
+}
+
+/// Parameters at the center of the pieces of the control grid small enough to start
+/// Newton iterations from, among those whose bounding box is hit by the ray.
+fn collect_ray_seeds(
+    ray: &Ray,
+    grid: Vec<Vec<Vec3>>,
+    ((u0, u1), (v0, v1)): ((f32, f32), (f32, f32)),
+    min_size: f32,
+    depth: usize,
+    seeds: &mut Vec<(f32, f32)>,
+) {
+    let (min, max) = bounds(grid.iter().flatten());
+    if ray.intersect_aabb(&Aabb { min, max }).is_none() {
+        return;
+    }
+    let (um, vm) = ((u0 + u1) / 2.0, (v0 + v1) / 2.0);
+    if depth >= MAX_RAY_SUBDIVISION_DEPTH || glm::distance(&min, &max) <= min_size {
+        seeds.push((um, vm));
+        return;
+    }
+
+    // split every row along v, then every column of both halves along u
+    let (low_v, high_v): (Vec<Vec<Vec3>>, Vec<Vec<Vec3>>) =
+        grid.iter().map(|row| subdivide(0.5, row)).unzip();
+    for (half, v_range) in [(low_v, (v0, vm)), (high_v, (vm, v1))] {
+        let columns = half[0].len();
+        let mut low_u = vec![Vec::with_capacity(columns); half.len()];
+        let mut high_u = vec![Vec::with_capacity(columns); half.len()];
+        for j in 0..columns {
+            let column: Vec<Vec3> = half.iter().map(|row| row[j]).collect();
+            let (low, high) = subdivide(0.5, &column);
+            for (i, (l, h)) in low.into_iter().zip(high).enumerate() {
+                low_u[i].push(l);
+                high_u[i].push(h);
             }
         }
+        collect_ray_seeds(ray, low_u, ((u0, um), v_range), min_size, depth + 1, seeds);
+        collect_ray_seeds(ray, high_u, ((um, u1), v_range), min_size, depth + 1, seeds);
     }
+}
 
-    fn calculate_normals(&mut self) {
-        for i in 0..(self.mesh_edges - 1) {
-            for j in 0..(self.mesh_edges - 1) {
-                let p0 = &self.mesh.vertices[i * self.mesh_edges + j].position;
-                let p1 = &self.mesh.vertices[i * self.mesh_edges + j + 1].position;
-                let p2 = &self.mesh.vertices[i * self.mesh_edges + j + self.mesh_edges].position;
-                let p3 =
-                    &self.mesh.vertices[i * self.mesh_edges + j + self.mesh_edges + 1].position;
+/// Tessellates several patches into a single mesh, see [`BezierSurface::tessellate_adaptive`].
+pub fn tessellate_patches<const M: usize, const N: usize>(
+    patches: &[BezierSurface<M, N>],
+    tolerance: SurfaceTolerance,
+    layout: UvLayout,
+) -> Mesh {
+    let mut mesh = Mesh {
+        vertices: Vec::new(),
+        indices: Vec::new(),
+    };
+    for (index, patch) in patches.iter().enumerate() {
+        let mut patch_mesh = patch.tessellate_adaptive(tolerance);
+        layout.apply(&mut patch_mesh, index, patches.len());
+        mesh.append(patch_mesh);
+    }
+    mesh
+}
 
-                let v0 = p2 - p0;
-                let v1 = p1 - p0;
-                let v2 = p3 - p1;
+/// Texture coordinates of the patches of a multi-patch model.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum UvLayout {
+    /// Every patch maps its `(u, v)` parameters to the whole texture.
+    Patch,
+    /// Patches are packed in a grid of cells covering one texture, each cell
+    /// keeping a margin of `padding`, in uv units, against texture bleeding.
+    Atlas { padding: f32 },
+}
 
-                if i == self.mesh_edges - 2 {
-                    let v3 = p3 - p2;
-                    self.mesh.vertices[(i + 1) * self.mesh_edges + j].normal +=
-                        glm::normalize(&glm::cross(&v0, &v3));
+impl UvLayout {
+    /// Maps the `(u, v)` parameters of the patch `index` out of `count` to texture coordinates.
+    pub fn map(&self, uv: Vec2, index: usize, count: usize) -> Vec2 {
+        match *self {
+            Self::Patch => uv,
+            Self::Atlas { padding } => {
+                let columns = (count as f32).sqrt().ceil().max(1.0) as usize;
+                let rows = count.div_ceil(columns).max(1);
+                let size = Vec2::new(1.0 / columns as f32, 1.0 / rows as f32);
+                let origin = Vec2::new((index % columns) as f32, (index / columns) as f32)
+                    .component_mul(&size);
+                let padding = Vec2::repeat(padding).inf(&(size * 0.5));
+                origin + padding + uv.component_mul(&(size - padding * 2.0))
+            }
+        }
+    }
 
-                    if i == j {
-                        self.mesh.vertices[(i + 1) * self.mesh_edges + j + 1].normal +=
-                            glm::normalize(&glm::cross(&v2, &v3));
-                    }
-                }
+    /// Replaces the `(u, v)` parameters stored in the uv of `mesh`, the tessellation
+    /// of the patch `index` out of `count`.
+    pub fn apply(&self, mesh: &mut Mesh, index: usize, count: usize) {
+        for vertex in mesh.vertices.iter_mut() {
+            vertex.uv = self.map(vertex.uv, index, count);
+        }
+    }
+}
 
-                if j == self.mesh_edges - 2 {
-                    self.mesh.vertices[i * self.mesh_edges + j + 1].normal +=
-                        glm::normalize(&glm::cross(&v2, &v1));
-                }
+#[derive(Clone, Copy, Debug)]
+pub enum SurfaceTolerance {
+    /// Maximum distance between the surface and its triangles.
+    World(f32),
+    /// Maximum error in pixels once projected by a perspective camera at `eye`,
+    /// `fov_y` being its vertical field of view in radians.
+    Screen {
+        pixels: f32,
+        eye: Vec3,
+        fov_y: f32,
+        viewport_height: f32,
+    },
+}
 
-                self.mesh.vertices[i * self.mesh_edges + j].normal =
-                    glm::normalize(&glm::cross(&v0, &v1));
+impl SurfaceTolerance {
+    /// World space tolerance for geometry bounded by `points`, taken at their closest to the eye.
+    fn world(&self, points: &[Vec3]) -> f32 {
+        match *self {
+            Self::World(tolerance) => tolerance,
+            Self::Screen {
+                pixels,
+                eye,
+                fov_y,
+                viewport_height,
+            } => {
+                let distance = points
+                    .iter()
+                    .map(|p| glm::distance(p, &eye))
+                    .fold(f32::INFINITY, f32::min);
+                pixels * distance * 2.0 * (fov_y / 2.0).tan() / viewport_height
             }
         }
     }
 }
+
+const MAX_ADAPTIVE_STEPS: usize = 64;
+
+/// Number of uniform steps keeping a polyline within `tolerance` of every Bezier curve in `curves`,
+/// from the bound `h^2 / 8 * max|C''|` of the linear interpolation error over a step `h`.
+fn curve_steps(curves: &[Vec<Vec3>], tolerance: f32) -> usize {
+    let mut bound: f32 = 0.0;
+    for curve in curves {
+        let degree = (curve.len() - 1) as f32;
+        for w in curve.windows(3) {
+            bound = bound.max((w[0] - w[1] * 2.0 + w[2]).norm() * degree * (degree - 1.0));
+        }
+    }
+    if bound == 0.0 {
+        return 1;
+    }
+    if tolerance <= 0.0 {
+        return MAX_ADAPTIVE_STEPS;
+    }
+    ((bound / (8.0 * tolerance)).sqrt().ceil() as usize).clamp(1, MAX_ADAPTIVE_STEPS)
+}
+
+/// `steps + 1` points along the Bezier curve. The curve is always evaluated in the same
+/// orientation, whichever way its control points are given, so that two patches sharing
+/// it get bitwise identical points.
+fn edge_points(curve: &[Vec3], steps: usize) -> Vec<Vec3> {
+    let (first, last) = (curve[0], curve[curve.len() - 1]);
+    let reversed = (last.x, last.y, last.z) < (first.x, first.y, first.z);
+    let canonical: Vec<Vec3> = if reversed {
+        curve.iter().rev().copied().collect()
+    } else {
+        curve.to_vec()
+    };
+    let mut points: Vec<Vec3> = (0..=steps)
+        .map(|k| de_casteljau(k as f32 / steps as f32, &canonical))
+        .collect();
+    if reversed {
+        points.reverse();
+    }
+    points
+}
+
+/// Triangulates the band between two counter-clockwise loops of `(index, s)`, `s` being
+/// the position of the vertex along its loop in `[0, 4)`.
+fn zip_loops(outer: &[(u32, f32)], inner: &[(u32, f32)]) -> Vec<u32> {
+    let next = |lp: &[(u32, f32)], k: usize| if k + 1 < lp.len() { lp[k + 1].1 } else { 4.0 };
+    let (mut a, mut b) = (0, 0);
+    let mut indices = Vec::with_capacity(3 * (outer.len() + inner.len()));
+    while a < outer.len() || b < inner.len() {
+        let o = outer[a % outer.len()].0;
+        let i = inner[b % inner.len()].0;
+        if b == inner.len() || (a < outer.len() && next(outer, a) <= next(inner, b)) {
+            indices.extend([o, outer[(a + 1) % outer.len()].0, i]);
+            a += 1;
+        } else {
+            indices.extend([o, inner[(b + 1) % inner.len()].0, i]);
+            b += 1;
+        }
+    }
+    indices
+}
+
+/// Indices of a regular grid of vertices stored row by row, two counter-clockwise triangles per cell.
+pub(crate) fn grid_indices(columns: usize, rows: usize) -> Vec<u32> {
+    let mut indices = Vec::with_capacity((columns - 1) * (rows - 1) * 6);
+    for j in 0..(rows - 1) {
+        for i in 0..(columns - 1) {
+            let a = (j * columns + i) as u32;
+            let b = a + 1;
+            let c = a + columns as u32;
+            let d = c + 1;
+            indices.extend([a, b, c, b, d, c]);
+        }
+    }
+    indices
+}
+
+/// Options of [`NurbsSurface::loft`].
+#[derive(Clone, Copy, Debug, Default)]
+pub struct LoftOptions {
+    /// Comes back smoothly from the last section to the first one.
+    pub closed: bool,
+    /// Direction in which the surface leaves the first section, scaled by the chord length
+    /// of the loft. Ignored by closed lofts.
+    pub start_tangent: Option<Vec3>,
+    /// Direction in which the surface reaches the last section, like `start_tangent`.
+    pub end_tangent: Option<Vec3>,
+}
+
+// NurbsSurface
+#[derive(Clone)]
+pub struct NurbsSurface {
+    degree_u: usize,
+    degree_v: usize,
+    ctrl_grid: Vec<Vec<Vec3>>,
+    weights: Vec<Vec<f32>>,
+    knots_u: Vec<f32>,
+    knots_v: Vec<f32>,
+}
+
+impl NurbsSurface {
+    /// `ctrl_grid[i][j]` is the control point `i` along `u` and `j` along `v`.
+    pub fn new(
+        degree_u: usize,
+        degree_v: usize,
+        ctrl_grid: Vec<Vec<Vec3>>,
+        weights: Vec<Vec<f32>>,
+        knots_u: Vec<f32>,
+        knots_v: Vec<f32>,
+    ) -> Result<Self, SplineError> {
+        let columns = ctrl_grid.first().map_or(0, |row| row.len());
+        if ctrl_grid.iter().any(|row| row.len() != columns) {
+            return Err(SplineError::IrregularControlGrid);
+        }
+        validate_knots(degree_u, ctrl_grid.len(), &knots_u)?;
+        validate_knots(degree_v, columns, &knots_v)?;
+        if weights.len() != ctrl_grid.len()
+            || weights
+                .iter()
+                .any(|row| row.len() != columns || row.iter().any(|&w| w <= 0.0))
+        {
+            return Err(SplineError::InvalidWeights);
+        }
+        Ok(Self {
+            degree_u,
+            degree_v,
+            ctrl_grid,
+            weights,
+            knots_u,
+            knots_v,
+        })
+    }
+
+    /// Linear extrusion of `curve` along `direction`, `v` running from the curve to its translated copy.
+    pub fn extrude(curve: &NurbsCurve, direction: Vec3) -> Self {
+        Self {
+            degree_u: curve.degree(),
+            degree_v: 1,
+            ctrl_grid: curve
+                .ctrl_points()
+                .iter()
+                .map(|&p| vec![p, p + direction])
+                .collect(),
+            weights: curve.weights().iter().map(|&w| vec![w, w]).collect(),
+            knots_u: curve.knots().to_vec(),
+            knots_v: vec![0.0, 0.0, 1.0, 1.0],
+        }
+    }
+
+    /// Exact open cylinder standing on `base_center`, with normals pointing outward.
+    pub fn cylinder(base_center: Vec3, axis: Vec3, radius: f32, height: f32) -> Self {
+        let axis = glm::normalize(&axis);
+        let helper = if axis.x.abs() < 0.9 {
+            Vec3::new(1.0, 0.0, 0.0)
+        } else {
+            Vec3::new(0.0, 1.0, 0.0)
+        };
+        let x_axis = glm::normalize(&glm::cross(&axis, &helper));
+        let y_axis = glm::cross(&axis, &x_axis);
+        let circle = NurbsCurve::circle(base_center, x_axis, y_axis, radius);
+        Self::extrude(&circle, axis * height)
+    }
+
+    /// Skins a surface through the cross sections, `u` following the curves and `v` going
+    /// from one section to the next. The sections must have the same degree, their domains
+    /// are mapped to `[0, 1]` and their knots merged. Along `v` the sections are interpolated
+    /// by a cubic spline with chord length parameters.
+    pub fn loft(sections: &[NurbsCurve], options: LoftOptions) -> Result<Self, SplineError> {
+        let count = sections.len();
+        if count < 2 || (options.closed && count < 3) {
+            return Err(SplineError::NotEnoughSections);
+        }
+        let degree_u = sections[0].degree();
+        if sections.iter().any(|section| section.degree() != degree_u) {
+            return Err(SplineError::DegreeMismatch);
+        }
+        let (knots_u, rows) = compatible_sections(sections)?;
+
+        // distance between sections, averaged over their control points
+        let distance = |a: &[Vec4], b: &[Vec4]| {
+            let sum: f32 = a
+                .iter()
+                .zip(b)
+                .map(|(p, q)| glm::distance(&(p.xyz() / p.w), &(q.xyz() / q.w)))
+                .sum();
+            sum / a.len() as f32
+        };
+        let spans = if options.closed { count } else { count - 1 };
+        let mut parameters = vec![0.0];
+        for k in 0..spans {
+            parameters.push(parameters[k] + distance(&rows[k], &rows[(k + 1) % count]));
+        }
+        let length = parameters[spans];
+        if parameters.windows(2).any(|w| w[1] <= w[0]) {
+            return Err(SplineError::SingularInterpolation);
+        }
+        parameters.iter_mut().for_each(|v| *v /= length);
+
+        let interpolation = if options.closed {
+            interpolate_periodic(&rows, &parameters)?
+        } else {
+            interpolate_clamped(
+                &rows,
+                &parameters,
+                options.start_tangent.map(|t| t * length),
+                options.end_tangent.map(|t| t * length),
+            )?
+        };
+
+        let grid = &interpolation.grid;
+        let ctrl_grid = (0..knots_u.len() - degree_u - 1)
+            .map(|i| grid.iter().map(|row| row[i].xyz() / row[i].w).collect())
+            .collect();
+        let weights = (0..knots_u.len() - degree_u - 1)
+            .map(|i| grid.iter().map(|row| row[i].w).collect())
+            .collect();
+        Self::new(
+            degree_u,
+            interpolation.degree,
+            ctrl_grid,
+            weights,
+            knots_u,
+            interpolation.knots,
+        )
+    }
+
+    pub fn domain(&self) -> ((f32, f32), (f32, f32)) {
+        let (rows, columns) = (self.ctrl_grid.len(), self.ctrl_grid[0].len());
+        (
+            (self.knots_u[self.degree_u], self.knots_u[rows]),
+            (self.knots_v[self.degree_v], self.knots_v[columns]),
+        )
+    }
+
+    pub fn point(&self, u: f32, v: f32) -> Vec3 {
+        self.partial_derivatives(u, v).0
+    }
+
+    /// Position and first partial derivatives `(S, dS/du, dS/dv)` at `(u, v)`.
+    pub fn partial_derivatives(&self, u: f32, v: f32) -> (Vec3, Vec3, Vec3) {
+        let (pu, pv) = (self.degree_u, self.degree_v);
+        let span_u = find_span(u, pu, &self.knots_u);
+        let span_v = find_span(v, pv, &self.knots_v);
+        let nu = basis_functions_derivatives(span_u, u, pu, &self.knots_u, 1);
+        let nv = basis_functions_derivatives(span_v, v, pv, &self.knots_v, 1);
+
+        // a[k][l]: derivative k times along u and l times along v, in homogeneous coordinates
+        let mut a = [[Vec4::zeros(); 2]; 2];
+        for (i, (nu0, nu1)) in nu[0].iter().zip(&nu[1]).enumerate() {
+            for (j, (nv0, nv1)) in nv[0].iter().zip(&nv[1]).enumerate() {
+                let (ci, cj) = (span_u - pu + i, span_v - pv + j);
+                let p = homogeneous(self.ctrl_grid[ci][cj], self.weights[ci][cj]);
+                a[0][0] += p * (nu0 * nv0);
+                a[1][0] += p * (nu1 * nv0);
+                a[0][1] += p * (nu0 * nv1);
+            }
+        }
+
+        let w = a[0][0].w;
+        let s = a[0][0].xyz() / w;
+        let su = (a[1][0].xyz() - s * a[1][0].w) / w;
+        let sv = (a[0][1].xyz() - s * a[0][1].w) / w;
+        (s, su, sv)
+    }
+
+    /// Unit normal `dS/du x dS/dv`. Where the surface is degenerate (collapsed edge,
+    /// pole) the normal is taken slightly inside the domain instead.
+    pub fn normal(&self, u: f32, v: f32) -> Vec3 {
+        let (_, su, sv) = self.partial_derivatives(u, v);
+        let n = glm::cross(&su, &sv);
+        if !is_degenerate(&n, &su, &sv) {
+            return glm::normalize(&n);
+        }
+        let ((u0, u1), (v0, v1)) = self.domain();
+        let (_, su, sv) = self.partial_derivatives(nudge(u, u0, u1), nudge(v, v0, v1));
+        let n = glm::cross(&su, &sv);
+        if n.norm_squared() > 0.0 {
+            glm::normalize(&n)
+        } else {
+            n
+        }
+    }
+
+    /// Samples `resolution_u * resolution_v` vertices evenly over the parameter domain.
+    pub fn tessellate(&self, resolution_u: usize, resolution_v: usize) -> Mesh {
+        let (resolution_u, resolution_v) = (resolution_u.max(2), resolution_v.max(2));
+        let ((u0, u1), (v0, v1)) = self.domain();
+        let mut vertices = Vec::with_capacity(resolution_u * resolution_v);
+        for j in 0..resolution_v {
+            let tv = j as f32 / (resolution_v - 1) as f32;
+            let v = v0 * (1.0 - tv) + v1 * tv;
+            for i in 0..resolution_u {
+                let tu = i as f32 / (resolution_u - 1) as f32;
+                let u = u0 * (1.0 - tu) + u1 * tu;
+                vertices.push(Vertex {
+                    position: self.point(u, v),
+                    normal: self.normal(u, v),
+                    uv: Vec2::new(tu, tv),
+                });
+            }
+        }
+        Mesh {
+            vertices,
+            indices: grid_indices(resolution_u, resolution_v),
+        }
+    }
+}
+
+impl<const M: usize, const N: usize> From<&BezierSurface<M, N>> for NurbsSurface {
+    fn from(surface: &BezierSurface<M, N>) -> NurbsSurface {
+        let clamped = |order: usize| {
+            let mut knots = vec![0.0; order];
+            knots.extend(vec![1.0; order]);
+            knots
+        };
+        NurbsSurface {
+            degree_u: M - 1,
+            degree_v: N - 1,
+            ctrl_grid: surface.ctrl_grid.iter().map(|row| row.to_vec()).collect(),
+            weights: vec![vec![1.0; N]; M],
+            knots_u: clamped(M),
+            knots_v: clamped(N),
+        }
+    }
+}
+
+/// Shared knot vector of the sections over `[0, 1]` and their homogeneous control points
+/// refined to it by knot insertion.
+fn compatible_sections(sections: &[NurbsCurve]) -> Result<(Vec<f32>, Vec<Vec<Vec4>>), SplineError> {
+    let normalized: Vec<Vec<f32>> = sections
+        .iter()
+        .map(|section| {
+            let (start, end) = section.domain();
+            section
+                .knots()
+                .iter()
+                .map(|k| (k - start) / (end - start))
+                .collect()
+        })
+        .collect();
+
+    // knots closer than the tolerance are considered equal
+    let mut distinct: Vec<f32> = normalized.iter().flatten().copied().collect();
+    distinct.sort_by(|a, b| a.total_cmp(b));
+    distinct.dedup_by(|b, a| *b - *a < KNOT_TOLERANCE);
+    let snap = |k: f32| {
+        distinct
+            .iter()
+            .copied()
+            .find(|d| (k - d).abs() < KNOT_TOLERANCE)
+            .unwrap_or(k)
+    };
+    let multiplicity = |knots: &[f32], d: f32| knots.iter().filter(|&&k| k == d).count();
+    let normalized: Vec<Vec<f32>> = normalized
+        .into_iter()
+        .map(|knots| knots.into_iter().map(snap).collect())
+        .collect();
+
+    let degree = sections[0].degree();
+    let mut rows = Vec::with_capacity(sections.len());
+    let mut knot_vectors = Vec::with_capacity(sections.len());
+    for (section, mut knots) in sections.iter().zip(normalized.iter().cloned()) {
+        let mut points: Vec<Vec4> = section
+            .ctrl_points()
+            .iter()
+            .zip(section.weights())
+            .map(|(&p, &w)| homogeneous(p, w))
+            .collect();
+        for &d in distinct.iter().filter(|&&d| d > 0.0 && d < 1.0) {
+            let target = normalized
+                .iter()
+                .map(|k| multiplicity(k, d))
+                .max()
+                .unwrap_or(0);
+            for _ in multiplicity(&knots, d)..target {
+                insert_knot(degree, &mut points, &mut knots, d);
+            }
+        }
+        rows.push(points);
+        knot_vectors.push(knots);
+    }
+    let knots = knot_vectors[0].clone();
+    if let Some(other) = knot_vectors.iter().find(|k| **k != knots) {
+        return Err(SplineError::InvalidKnotCount {
+            expected: knots.len(),
+            found: other.len(),
+        });
+    }
+    Ok((knots, rows))
+}
+
+const KNOT_TOLERANCE: f32 = 1e-5;
+
+/// Boehm's knot insertion on homogeneous control points.
+fn insert_knot(degree: usize, points: &mut Vec<Vec4>, knots: &mut Vec<f32>, t: f32) {
+    let p = degree;
+    let k = find_span(t, p, knots);
+    let mut refined = Vec::with_capacity(points.len() + 1);
+    refined.extend_from_slice(&points[..=k - p]);
+    for i in (k - p + 1)..=k {
+        let denom = knots[i + p] - knots[i];
+        let alpha = if denom == 0.0 {
+            0.0
+        } else {
+            (t - knots[i]) / denom
+        };
+        refined.push(points[i - 1] * (1.0 - alpha) + points[i] * alpha);
+    }
+    refined.extend_from_slice(&points[k..]);
+    *points = refined;
+    knots.insert(k + 1, t);
+}
+
+/// Values of the basis functions or their derivatives at `t`, one per control point.
+fn basis_row(t: f32, degree: usize, knots: &[f32], order: usize) -> Vec<f32> {
+    let span = find_span(t, degree, knots);
+    let ders = basis_functions_derivatives(span, t, degree, knots, order);
+    let mut row = vec![0.0; knots.len() - degree - 1];
+    for (j, &value) in ders[order].iter().enumerate() {
+        row[span - degree + j] = value;
+    }
+    row
+}
+
+/// Solves for rows of homogeneous control points, the columns of `rhs` being independent.
+fn solve_rows(matrix: Vec<Vec<f32>>, rhs: &[Vec<Vec4>]) -> Result<Vec<Vec<Vec4>>, SplineError> {
+    let flat = rhs
+        .iter()
+        .map(|row| row.iter().flat_map(|p| [p.x, p.y, p.z, p.w]).collect())
+        .collect();
+    let solution = solve_linear_system(matrix, flat).ok_or(SplineError::SingularInterpolation)?;
+    Ok(solution
+        .iter()
+        .map(|row| {
+            row.chunks_exact(4)
+                .map(|c| Vec4::new(c[0], c[1], c[2], c[3]))
+                .collect()
+        })
+        .collect())
+}
+
+/// Spline along `v` through the sections, `grid[j][i]` being the homogeneous control
+/// point `j` along `v` and `i` along `u`.
+struct Interpolation {
+    degree: usize,
+    knots: Vec<f32>,
+    grid: Vec<Vec<Vec4>>,
+}
+
+/// Clamped spline through `rows` at `parameters`, with knots averaged from the parameters
+/// (The NURBS Book, 9.2.1). An end tangent adds a derivative condition and a control point.
+fn interpolate_clamped(
+    rows: &[Vec<Vec4>],
+    parameters: &[f32],
+    start: Option<Vec3>,
+    end: Option<Vec3>,
+) -> Result<Interpolation, SplineError> {
+    let mut extended = parameters.to_vec();
+    if start.is_some() {
+        extended.insert(0, 0.0);
+    }
+    if end.is_some() {
+        extended.push(1.0);
+    }
+    let n = extended.len();
+    let degree = 3.min(n - 1);
+    let mut knots = vec![0.0; degree + 1];
+    knots.extend(
+        (1..n - degree).map(|j| extended[j..j + degree].iter().sum::<f32>() / degree as f32),
+    );
+    knots.extend(vec![1.0; degree + 1]);
+
+    // the weights do not change across the end, so that the tangent is the same for all of u
+    let derivative = |row: &[Vec4], t: Vec3| -> Vec<Vec4> {
+        row.iter()
+            .map(|p| Vec4::new(t.x * p.w, t.y * p.w, t.z * p.w, 0.0))
+            .collect()
+    };
+    let mut matrix = Vec::with_capacity(n);
+    let mut rhs = Vec::with_capacity(n);
+    if let Some(t) = start {
+        matrix.push(basis_row(0.0, degree, &knots, 1));
+        rhs.push(derivative(&rows[0], t));
+    }
+    for (row, &v) in rows.iter().zip(parameters) {
+        matrix.push(basis_row(v, degree, &knots, 0));
+        rhs.push(row.clone());
+    }
+    if let Some(t) = end {
+        matrix.push(basis_row(1.0, degree, &knots, 1));
+        rhs.push(derivative(&rows[rows.len() - 1], t));
+    }
+    Ok(Interpolation {
+        degree,
+        knots,
+        grid: solve_rows(matrix, &rhs)?,
+    })
+}
+
+/// Periodic spline through `rows`, `parameters` ending with 1 for the return to the first
+/// row. The knots are the parameters, wrapped around past both ends of the domain.
+fn interpolate_periodic(
+    rows: &[Vec<Vec4>],
+    parameters: &[f32],
+) -> Result<Interpolation, SplineError> {
+    let count = rows.len();
+    let degree = 3.min(count - 1);
+    let mut knots = Vec::with_capacity(count + 2 * degree + 1);
+    knots.extend((1..=degree).rev().map(|m| parameters[count - m] - 1.0));
+    knots.extend_from_slice(parameters);
+    knots.extend((1..=degree).map(|m| parameters[m] + 1.0));
+
+    // the last `degree` control points repeat the first ones
+    let matrix = parameters[..count]
+        .iter()
+        .map(|&v| {
+            let full = basis_row(v, degree, &knots, 0);
+            let mut row = vec![0.0; count];
+            for (i, value) in full.into_iter().enumerate() {
+                row[i % count] += value;
+            }
+            row
+        })
+        .collect();
+    let solution = solve_rows(matrix, rows)?;
+    let grid = (0..count + degree)
+        .map(|i| solution[i % count].clone())
+        .collect();
+    Ok(Interpolation {
+        degree,
+        knots,
+        grid,
+    })
+}
+
+fn is_degenerate(normal: &Vec3, du: &Vec3, dv: &Vec3) -> bool {
+    normal.norm_squared() <= f32::EPSILON * du.norm_squared() * dv.norm_squared()
+}
+
+/// Moves `t` a small step from the closest bound of `[start, end]` toward the inside.
+fn nudge(t: f32, start: f32, end: f32) -> f32 {
+    let step = (end - start) * 1e-3;
+    if t - start < end - t {
+        t + step
+    } else {
+        t - step
+    }
+}
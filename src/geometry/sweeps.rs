@@ -0,0 +1,388 @@
+/*
+* SPDX-License-Identifier: MIT
+*/
+
+use std::f32::consts::FRAC_PI_6;
+use std::fmt;
+
+use nalgebra_glm as glm;
+
+use super::algorithms::{ear_clipping, signed_area_2d};
+use super::primitives::{Mesh, PolyLine, Vertex};
+use super::shapes::{revolve_profile, without_degenerate, ProfilePoint};
+use super::splines::{Curve, Frame};
+use super::surfaces::grid_indices;
+use super::{Vec2, Vec3};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SweepError {
+    NotEnoughPoints,
+    OpenProfile,
+    /// The profile is flat, self-intersecting or lies on the axis.
+    DegenerateProfile,
+    DegenerateAxis,
+}
+
+impl fmt::Display for SweepError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SweepError::NotEnoughPoints => write!(f, "not enough profile points"),
+            SweepError::OpenProfile => write!(f, "profile must be closed"),
+            SweepError::DegenerateProfile => write!(f, "profile is degenerate"),
+            SweepError::DegenerateAxis => write!(f, "axis has no direction"),
+        }
+    }
+}
+
+/// Profile corners sharper than this get a normal for each side.
+const CREASE_ANGLE: f32 = FRAC_PI_6;
+
+/// Vertex of a profile: the index of its point, its unit tangent and its arc length
+/// parameter in `[0, 1]`.
+struct SectionPoint {
+    index: usize,
+    tangent: Vec2,
+    u: f32,
+}
+
+fn normalize_2d(v: Vec2) -> Vec2 {
+    if v.norm_squared() > 0.0 {
+        glm::normalize(&v)
+    } else {
+        v
+    }
+}
+
+/// Tangents of the polyline `points`, a closed one ending with its first point again.
+/// Points on corners sharper than [`CREASE_ANGLE`] are repeated with the tangent of each side.
+fn section(points: &[Vec2], closed: bool) -> Vec<SectionPoint> {
+    let count = points.len();
+    let direction = |from: usize| normalize_2d(points[(from + 1) % count] - points[from]);
+    let mut lengths = vec![0.0];
+    let segments = if closed { count } else { count - 1 };
+    for i in 0..segments {
+        let step = glm::distance(&points[i], &points[(i + 1) % count]);
+        lengths.push(lengths[i] + step);
+    }
+    let total = lengths[segments].max(f32::EPSILON);
+
+    let mut section = Vec::with_capacity(count * 2 + 1);
+    for (i, &length) in lengths.iter().enumerate().take(count) {
+        let u = length / total;
+        let incoming = (closed || i > 0).then(|| direction((i + count - 1) % count));
+        let outgoing = (closed || i + 1 < count).then(|| direction(i));
+        match (incoming, outgoing) {
+            (Some(incoming), Some(outgoing))
+                if glm::dot(&incoming, &outgoing) < CREASE_ANGLE.cos() =>
+            {
+                if i > 0 {
+                    section.push(SectionPoint {
+                        index: i,
+                        tangent: incoming,
+                        u,
+                    });
+                }
+                section.push(SectionPoint {
+                    index: i,
+                    tangent: outgoing,
+                    u,
+                });
+            }
+            (incoming, outgoing) => {
+                let sum = incoming.unwrap_or_default() + outgoing.unwrap_or_default();
+                section.push(SectionPoint {
+                    index: i,
+                    tangent: normalize_2d(sum),
+                    u,
+                });
+            }
+        }
+    }
+    if closed {
+        // the first point again, with the tangent arriving at it
+        let incoming = direction(count - 1);
+        let outgoing = direction(0);
+        let tangent = if glm::dot(&incoming, &outgoing) < CREASE_ANGLE.cos() {
+            incoming
+        } else {
+            normalize_2d(incoming + outgoing)
+        };
+        section.push(SectionPoint {
+            index: 0,
+            tangent,
+            u: 1.0,
+        });
+    }
+    section
+}
+
+/// Points of `profile` without repetitions, along with whether it is closed.
+fn profile_points(profile: &PolyLine) -> (Vec<Vec3>, bool) {
+    let closed = profile.is_closed() && profile.points.len() > 1;
+    let mut points = profile.points.clone();
+    points.dedup();
+    if closed && points.len() > 1 && points.first() == points.last() {
+        points.pop();
+    }
+    (points, closed)
+}
+
+/// Triangulated polygon facing `normal`, its uv fitting the polygon bounds.
+fn cap(points: &[Vec3], polygon: &[Vec2], normal: Vec3, flip: bool) -> Result<Mesh, SweepError> {
+    let triangles = ear_clipping(polygon).ok_or(SweepError::DegenerateProfile)?;
+    let (min, max) = polygon.iter().fold(
+        (Vec2::repeat(f32::INFINITY), Vec2::repeat(f32::NEG_INFINITY)),
+        |(min, max), p| (glm::min2(&min, p), glm::max2(&max, p)),
+    );
+    let size = glm::max2(&(max - min), &Vec2::repeat(f32::EPSILON));
+    let vertices = points
+        .iter()
+        .zip(polygon)
+        .map(|(&position, p)| Vertex {
+            position,
+            normal,
+            uv: (p - min).component_div(&size),
+        })
+        .collect();
+    let indices = triangles
+        .iter()
+        .flat_map(|&[a, b, c]| if flip { [a, c, b] } else { [a, b, c] })
+        .map(|i| i as u32)
+        .collect();
+    Ok(Mesh { vertices, indices })
+}
+
+/// Direction from the axis to `point`, `None` on the axis.
+fn radial(point: &Vec3, origin: &Vec3, axis: &Vec3) -> Option<Vec3> {
+    let offset = point - origin;
+    let radial = offset - axis * glm::dot(axis, &offset);
+    (radial.norm() > 1e-6 * offset.norm().max(f32::EPSILON)).then(|| glm::normalize(&radial))
+}
+
+/// Revolves the profile given by `points` and `tangents` around the axis and moves the
+/// result from the y axis of [`revolve_profile`] to the actual axis.
+fn revolve_points(
+    points: &[Vec3],
+    tangents: &[Vec3],
+    origin: Vec3,
+    axis: Vec3,
+    angle: f32,
+    segments: usize,
+) -> Result<Mesh, SweepError> {
+    if axis.norm_squared() == 0.0 {
+        return Err(SweepError::DegenerateAxis);
+    }
+    let axis = glm::normalize(&axis);
+    let reference = points
+        .iter()
+        .find_map(|p| radial(p, &origin, &axis))
+        .ok_or(SweepError::DegenerateProfile)?;
+
+    let profile: Vec<ProfilePoint> = points
+        .iter()
+        .zip(tangents)
+        .map(|(p, tangent)| {
+            let offset = p - origin;
+            let radial = radial(p, &origin, &axis).unwrap_or(reference);
+            let tangent = normalize_2d(Vec2::new(
+                glm::dot(tangent, &radial),
+                glm::dot(tangent, &axis),
+            ));
+            ProfilePoint::new(
+                glm::dot(&offset, &radial),
+                glm::dot(&offset, &axis),
+                Vec2::new(tangent.y, -tangent.x),
+            )
+        })
+        .collect();
+
+    let mut mesh = revolve_profile(&profile, angle, segments);
+    let side = glm::cross(&reference, &axis);
+    for vertex in mesh.vertices.iter_mut() {
+        let (p, n) = (vertex.position, vertex.normal);
+        vertex.position = origin + reference * p.x + axis * p.y + side * p.z;
+        vertex.normal = reference * n.x + axis * n.y + side * n.z;
+    }
+    Ok(mesh)
+}
+
+/// Revolves `profile` by `angle` radians around the axis through `origin` along `axis`,
+/// counter-clockwise when the axis points towards the viewer. A closed profile faces
+/// outwards, an open one when it goes along the axis; u follows the rotation and v the profile.
+pub fn revolve(
+    profile: &PolyLine,
+    origin: Vec3,
+    axis: Vec3,
+    angle: f32,
+    segments: usize,
+) -> Result<Mesh, SweepError> {
+    let (mut points, closed) = profile_points(profile);
+    if points.len() < 2 {
+        return Err(SweepError::NotEnoughPoints);
+    }
+    if axis.norm_squared() == 0.0 {
+        return Err(SweepError::DegenerateAxis);
+    }
+    // corners are found in the plane of the profile
+    let unit_axis = glm::normalize(&axis);
+    let reference = points
+        .iter()
+        .find_map(|p| radial(p, &origin, &unit_axis))
+        .ok_or(SweepError::DegenerateProfile)?;
+    let mut planar: Vec<Vec2> = points
+        .iter()
+        .map(|p| {
+            let offset = p - origin;
+            let r = radial(p, &origin, &unit_axis).map_or(0.0, |radial| glm::dot(&offset, &radial));
+            Vec2::new(r, glm::dot(&offset, &unit_axis))
+        })
+        .collect();
+    if closed && signed_area_2d(&planar) < 0.0 {
+        points.reverse();
+        planar.reverse();
+    }
+    let section = section(&planar, closed);
+    let section_points: Vec<Vec3> = section.iter().map(|s| points[s.index]).collect();
+    // the profile tangent is in the plane of every point and the axis
+    let tangents: Vec<Vec3> = section
+        .iter()
+        .zip(&section_points)
+        .map(|(s, p)| {
+            let radial = radial(p, &origin, &unit_axis).unwrap_or(reference);
+            radial * s.tangent.x + unit_axis * s.tangent.y
+        })
+        .collect();
+    revolve_points(&section_points, &tangents, origin, axis, angle, segments)
+}
+
+/// Like [`revolve`] for a curve profile sampled at `samples + 1` evenly spaced parameters.
+pub fn revolve_curve<C: Curve>(
+    curve: &C,
+    samples: usize,
+    origin: Vec3,
+    axis: Vec3,
+    angle: f32,
+    segments: usize,
+) -> Result<Mesh, SweepError> {
+    let (start, end) = curve.domain();
+    let samples = samples.max(1);
+    let parameters: Vec<f32> = (0..=samples)
+        .map(|i| start + (end - start) * i as f32 / samples as f32)
+        .collect();
+    let points: Vec<Vec3> = parameters.iter().map(|&t| curve.point(t)).collect();
+    let tangents: Vec<Vec3> = parameters.iter().map(|&t| curve.tangent(t)).collect();
+    revolve_points(&points, &tangents, origin, axis, angle, segments)
+}
+
+/// Extrudes the closed planar `profile` by `direction`, closing both ends with
+/// ear clipped caps if `caps`. u follows the profile and v the extrusion.
+pub fn extrude(profile: &PolyLine, direction: Vec3, caps: bool) -> Result<Mesh, SweepError> {
+    let (mut points, closed) = profile_points(profile);
+    if !closed {
+        return Err(SweepError::OpenProfile);
+    }
+    if points.len() < 3 {
+        return Err(SweepError::NotEnoughPoints);
+    }
+    if direction.norm_squared() == 0.0 {
+        return Err(SweepError::DegenerateAxis);
+    }
+
+    // Newell's normal of the profile, turned towards the extrusion
+    let mut normal = Vec3::zeros();
+    for (i, p) in points.iter().enumerate() {
+        normal += glm::cross(p, &points[(i + 1) % points.len()]);
+    }
+    if normal.norm_squared() == 0.0 || glm::dot(&normal, &direction) == 0.0 {
+        return Err(SweepError::DegenerateProfile);
+    }
+    if glm::dot(&normal, &direction) < 0.0 {
+        points.reverse();
+        normal = -normal;
+    }
+    let normal = glm::normalize(&normal);
+    let e1 = glm::normalize(
+        &(points[1] - points[0] - normal * glm::dot(&normal, &(points[1] - points[0]))),
+    );
+    let e2 = glm::cross(&normal, &e1);
+    let planar: Vec<Vec2> = points
+        .iter()
+        .map(|p| Vec2::new(glm::dot(p, &e1), glm::dot(p, &e2)))
+        .collect();
+
+    let section = section(&planar, true);
+    let mut vertices = Vec::with_capacity(section.len() * 2);
+    for v in [0.0, 1.0] {
+        for s in &section {
+            let tangent = e1 * s.tangent.x + e2 * s.tangent.y;
+            vertices.push(Vertex {
+                position: points[s.index] + direction * v,
+                normal: glm::normalize(&glm::cross(&tangent, &direction)),
+                uv: Vec2::new(s.u, v),
+            });
+        }
+    }
+    let mut mesh = without_degenerate(Mesh {
+        vertices,
+        indices: grid_indices(section.len(), 2),
+    });
+
+    if caps {
+        let top: Vec<Vec3> = points.iter().map(|p| p + direction).collect();
+        mesh.append(cap(&points, &planar, -normal, true)?);
+        mesh.append(cap(&top, &planar, normal, false)?);
+    }
+    Ok(mesh)
+}
+
+/// Sweeps `profile`, given in the xy plane, along `path` through `samples` rotation
+/// minimizing frames, x following the normal of the frames and y their binormal.
+/// A closed profile faces outwards and may be capped at both ends, an open one faces
+/// the right of its direction. u follows the profile and v the path.
+pub fn sweep<C: Curve>(
+    profile: &PolyLine,
+    path: &C,
+    samples: usize,
+    caps: bool,
+) -> Result<Mesh, SweepError> {
+    let (points, closed) = profile_points(profile);
+    if points.len() < 2 {
+        return Err(SweepError::NotEnoughPoints);
+    }
+    if caps && !closed {
+        return Err(SweepError::OpenProfile);
+    }
+    let mut planar: Vec<Vec2> = points.iter().map(|p| p.xy()).collect();
+    if closed && signed_area_2d(&planar) < 0.0 {
+        planar.reverse();
+    }
+
+    let section = section(&planar, closed);
+    let frames = path.rotation_minimizing_frames(samples.max(2));
+    let at = |frame: &Frame, p: &Vec2| frame.origin + frame.normal * p.x + frame.binormal * p.y;
+    let mut vertices = Vec::with_capacity(section.len() * frames.len());
+    for (j, frame) in frames.iter().enumerate() {
+        let v = j as f32 / (frames.len() - 1) as f32;
+        for s in &section {
+            let normal = frame.normal * s.tangent.y - frame.binormal * s.tangent.x;
+            vertices.push(Vertex {
+                position: at(frame, &planar[s.index]),
+                normal: glm::normalize(&normal),
+                uv: Vec2::new(s.u, v),
+            });
+        }
+    }
+    let mut mesh = without_degenerate(Mesh {
+        vertices,
+        indices: grid_indices(section.len(), frames.len()),
+    });
+
+    if caps {
+        for (frame, flip) in [(&frames[0], true), (&frames[frames.len() - 1], false)] {
+            let ring: Vec<Vec3> = planar.iter().map(|p| at(frame, p)).collect();
+            let normal = if flip { -frame.tangent } else { frame.tangent };
+            mesh.append(cap(&ring, &planar, glm::normalize(&normal), flip)?);
+        }
+    }
+    Ok(mesh)
+}